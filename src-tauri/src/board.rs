@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use std::{
+    collections::HashMap,
+    format, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::clean::CleanStep;
+use crate::column::ColumnOperations;
+use crate::convert::ParseOptions;
+use crate::csv::CsvOptions;
+use crate::error::DataboardError;
+use crate::excel::ExcelOptions;
+use crate::state::{ColumnType, FailurePolicy, FileFormat, Query};
+
+// 项目文件格式版本, 结构变化时递增并在 migrate 中升级旧文件
+pub const BOARD_VERSION: u64 = 1;
+
+// 最近打开的项目数量
+const RECENT_LIMIT: usize = 10;
+const RECENT_FILE: &str = "recent.json";
+
+// 项目中的数据来源及其加载方式
+#[derive(Serialize, Deserialize)]
+pub struct BoardSource {
+    // 数据集名称
+    pub dataset: String,
+    // 数据文件, 相对路径按项目文件所在目录解析
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+    #[serde(default)]
+    pub csv: Option<CsvOptions>,
+    #[serde(default)]
+    pub excel: Option<ExcelOptions>,
+    // 多文件合并时是否添加来源文件列
+    #[serde(default)]
+    pub source_column: bool,
+    // 列类型配置, 即 Setting.columns
+    #[serde(default)]
+    pub columns: HashMap<String, ColumnType>,
+    // 转换失败的处理方式, 即 Setting.errors
+    #[serde(default)]
+    pub errors: HashMap<String, FailurePolicy>,
+    // 文本解析配置, 即 Setting.parse
+    #[serde(default)]
+    pub parse: HashMap<String, ParseOptions>,
+    // 清洗步骤, 即 Setting.cleaning
+    #[serde(default)]
+    pub cleaning: Vec<CleanStep>,
+    // 列操作, 即 Setting.operations
+    #[serde(default)]
+    pub operations: ColumnOperations,
+}
+
+// 保存的查询
+#[derive(Serialize, Deserialize)]
+pub struct BoardQuery {
+    pub name: String,
+    // 查询的数据集
+    pub dataset: String,
+    pub query: Query,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Board {
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub sources: Vec<BoardSource>,
+    #[serde(default)]
+    pub queries: Vec<BoardQuery>,
+    // 前端布局, 原样保存
+    #[serde(default)]
+    pub layout: JsonValue,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecentBoard {
+    pub path: String,
+    pub name: String,
+    // 最近打开时间, unix 秒
+    pub opened: u64,
+}
+
+// 旧版本项目文件逐级升级到当前版本
+fn migrate(mut value: JsonValue) -> Result<JsonValue, DataboardError> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| DataboardError::Parse("项目文件格式错误".to_string()))?;
+    // 未记录版本的文件按第一版处理
+    let version = object.get("version").and_then(|v| v.as_u64()).unwrap_or(1);
+    if version > BOARD_VERSION {
+        return Err(DataboardError::InvalidQuery(format!(
+            "项目文件版本 {} 高于当前支持的版本 {}",
+            version, BOARD_VERSION
+        )));
+    }
+    object.insert("version".to_string(), BOARD_VERSION.into());
+    Ok(value)
+}
+
+// 项目名称为空时使用文件名
+fn board_name(board: &Board, path: &Path) -> String {
+    if !board.name.trim().is_empty() {
+        return board.name.trim().to_string();
+    }
+    path.file_stem()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+pub fn save(path: &str, board: &mut Board) -> Result<(), DataboardError> {
+    board.version = BOARD_VERSION;
+    board.name = board_name(board, Path::new(path));
+    let text = serde_json::to_string_pretty(board)?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn open(path: &str) -> Result<Board, DataboardError> {
+    let text = fs::read_to_string(path)?;
+    let value = serde_json::from_str::<JsonValue>(&text)?;
+    let mut board: Board = serde_json::from_value(migrate(value)?)?;
+    board.name = board_name(&board, Path::new(path));
+
+    let base = Path::new(path).parent().unwrap_or(Path::new(""));
+    for source in board.sources.iter_mut() {
+        for file in source.paths.iter_mut() {
+            if Path::new(file.as_str()).is_relative() {
+                *file = base.join(file.as_str()).to_string_lossy().to_string();
+            }
+        }
+    }
+    Ok(board)
+}
+
+// 最近打开的项目, 已删除的文件不再列出
+pub fn recent(dir: &Path) -> Vec<RecentBoard> {
+    let list = fs::read_to_string(dir.join(RECENT_FILE))
+        .ok()
+        .and_then(|v| serde_json::from_str::<Vec<RecentBoard>>(&v).ok())
+        .unwrap_or_default();
+    list.into_iter()
+        .filter(|v| Path::new(&v.path).exists())
+        .collect()
+}
+
+// 记录最近打开的项目
+pub fn remember(dir: &Path, path: &str, name: &str) -> Result<(), DataboardError> {
+    let path = fs::canonicalize(path)
+        .unwrap_or_else(|_| PathBuf::from(path))
+        .to_string_lossy()
+        .to_string();
+    let opened = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0);
+
+    let mut list = recent(dir);
+    list.retain(|v| v.path != path);
+    list.insert(
+        0,
+        RecentBoard {
+            path,
+            name: name.to_string(),
+            opened,
+        },
+    );
+    list.truncate(RECENT_LIMIT);
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(RECENT_FILE), serde_json::to_string(&list)?)?;
+    Ok(())
+}
@@ -0,0 +1,462 @@
+use polars::lazy::dsl::{col, lit, when, Expr};
+use polars::prelude::*;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::format;
+
+use crate::convert::{self, ParseOptions};
+use crate::error::DataboardError;
+use crate::job::Job;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CleanMode {
+    // 删除重复行
+    Dedupe = 0,
+    // 填充空值
+    FillNull = 1,
+    // 全角字符转为半角
+    HalfWidth = 2,
+    // 转为小写
+    Lowercase = 3,
+    // 转为大写
+    Uppercase = 4,
+    // 连续空白合并为一个空格, 并去掉首尾空白
+    Whitespace = 5,
+    // 按分位数截断异常值
+    Clip = 6,
+}
+impl Serialize for CleanMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for CleanMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            0 => CleanMode::Dedupe,
+            1 => CleanMode::FillNull,
+            2 => CleanMode::HalfWidth,
+            3 => CleanMode::Lowercase,
+            4 => CleanMode::Uppercase,
+            5 => CleanMode::Whitespace,
+            6 => CleanMode::Clip,
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "不支持的清洗步骤 {}",
+                    value
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum FillMode {
+    // 使用 value 填充
+    #[default]
+    Constant = 0,
+    // 使用上一个非空值
+    Forward = 1,
+    // 使用下一个非空值
+    Backward = 2,
+    // 使用平均值
+    Mean = 3,
+    // 使用中位数
+    Median = 4,
+}
+impl Serialize for FillMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for FillMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => FillMode::Forward,
+            2 => FillMode::Backward,
+            3 => FillMode::Mean,
+            4 => FillMode::Median,
+            _ => FillMode::Constant,
+        })
+    }
+}
+
+// 清洗步骤, 按顺序作用于转换后的数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanStep {
+    pub mode: CleanMode,
+    // 处理的列, 为空时: 去重比较整行, 文本步骤处理全部文本列, 截断处理全部数值列, 填充处理全部列
+    #[serde(default)]
+    pub columns: Vec<String>,
+    // 空值填充方式
+    #[serde(default)]
+    pub fill: FillMode,
+    // 填充的常量, 按列类型转换
+    #[serde(default)]
+    pub value: Option<String>,
+    // 截断的下分位数, 0~1
+    #[serde(default)]
+    pub lower: Option<f64>,
+    // 截断的上分位数, 0~1
+    #[serde(default)]
+    pub upper: Option<f64>,
+}
+
+// 清洗步骤影响的行数
+#[derive(Serialize)]
+pub struct CleanReport {
+    pub mode: CleanMode,
+    pub affected: usize,
+}
+
+// 影响行数的统计方式, 保存的是该步骤前后的完整计划,
+// 统计时每个步骤都会从源数据重新执行之前的全部步骤, N 个步骤约扫描 N+1 次
+enum Affected {
+    // 处理前的数据中满足条件的行
+    Rows(Box<LazyFrame>, Expr),
+    // 处理前后的行数差
+    Removed(Box<LazyFrame>, Box<LazyFrame>),
+}
+
+// 清洗后的数据计划, 行数统计在 count 中执行
+pub struct CleanPlan {
+    pub records: LazyFrame,
+    steps: Vec<(CleanMode, Affected)>,
+}
+
+// 全角字符转为半角, 全角空格转为普通空格
+fn half_width(val: &str) -> String {
+    val.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+fn collapse_whitespace(val: &str) -> String {
+    val.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn map_text(name: &str, f: fn(&str) -> String) -> Expr {
+    col(name).map(
+        move |s| {
+            Ok(Some(
+                s.str()?
+                    .into_iter()
+                    .map(|op| op.map(f))
+                    .collect::<StringChunked>()
+                    .into_series(),
+            ))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+fn quantile(name: &str, value: f64, dtype: &DataType) -> Result<Expr, DataboardError> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(DataboardError::InvalidQuery(format!(
+            "列 {} 截断分位数应在 0~1 之间: {}",
+            name, value
+        )));
+    }
+    Ok(col(name)
+        .quantile(lit(value), QuantileInterpolOptions::Linear)
+        .cast(dtype.clone()))
+}
+
+// 填充的常量按列类型转换, 规则与数据配置的类型转换一致
+fn fill_value(name: &str, value: &str, dtype: &DataType) -> Result<Expr, DataboardError> {
+    let options = ParseOptions::default();
+    let text = Series::new(name, [value.trim()]);
+    let converted = match dtype {
+        DataType::String | DataType::Categorical(_, _) => Ok(Series::new(name, [value])),
+        DataType::Boolean => convert::to_boolean(&text),
+        DataType::Date => Ok(Series::new(name, [options.date(value.trim())])),
+        DataType::Datetime(unit, tz) => convert::to_datetime(&text, *unit, tz.as_deref(), &options),
+        DataType::Duration(unit) => convert::to_duration(&text, *unit),
+        DataType::Decimal(precision, scale) => {
+            convert::to_decimal(&text, *precision, scale.unwrap_or_default(), &options)
+        }
+        v if v.is_integer() => convert::to_int64(&text, &options),
+        v if v.is_float() => convert::to_float64(&text, &options),
+        _ => {
+            return Err(DataboardError::TypeMismatch(format!(
+                "列 {} 的类型 {} 不支持填充常量",
+                name, dtype
+            )))
+        }
+    };
+    // 超出列类型范围的数值也视为无法转换
+    let converted = converted.and_then(|v| v.cast(dtype)).ok();
+    match converted {
+        Some(v) if v.null_count() == 0 => Ok(lit(v).first()),
+        _ => Err(DataboardError::InvalidQuery(format!(
+            "列 {} 的填充值 {} 无法转换为 {}",
+            name, value, dtype
+        ))),
+    }
+}
+
+impl CleanStep {
+    // 处理的列及类型, 未指定时按步骤选取适用的列
+    fn targets(
+        &self,
+        schema: &Schema,
+        accept: fn(&DataType) -> bool,
+    ) -> Result<Vec<(String, DataType)>, DataboardError> {
+        if self.columns.is_empty() {
+            return Ok(schema
+                .iter()
+                .filter(|(_, dtype)| accept(dtype))
+                .map(|(name, dtype)| (name.to_string(), dtype.clone()))
+                .collect());
+        }
+        self.columns
+            .iter()
+            .map(|name| match schema.get(name) {
+                Some(dtype) if accept(dtype) => Ok((name.clone(), dtype.clone())),
+                Some(dtype) => Err(DataboardError::TypeMismatch(format!(
+                    "列 {} 的类型 {} 不支持该清洗步骤",
+                    name, dtype
+                ))),
+                None => Err(DataboardError::UnknownColumn(name.clone())),
+            })
+            .collect()
+    }
+
+    // 各列处理后的值
+    fn exprs(&self, schema: &Schema) -> Result<Vec<(String, Expr)>, DataboardError> {
+        let text = |dtype: &DataType| dtype == &DataType::String;
+        let numeric = |dtype: &DataType| dtype.is_numeric();
+        let any = |_: &DataType| true;
+        match self.mode {
+            CleanMode::HalfWidth
+            | CleanMode::Lowercase
+            | CleanMode::Uppercase
+            | CleanMode::Whitespace => {
+                let f: fn(&str) -> String = match self.mode {
+                    CleanMode::HalfWidth => half_width,
+                    CleanMode::Lowercase => |v| v.to_lowercase(),
+                    CleanMode::Uppercase => |v| v.to_uppercase(),
+                    _ => collapse_whitespace,
+                };
+                Ok(self
+                    .targets(schema, text)?
+                    .into_iter()
+                    .map(|(name, _)| {
+                        let expr = map_text(&name, f);
+                        (name, expr)
+                    })
+                    .collect())
+            }
+            CleanMode::Clip => {
+                if self.lower.is_none() && self.upper.is_none() {
+                    return Err(DataboardError::InvalidQuery(
+                        "截断需指定上分位数或下分位数".to_string(),
+                    ));
+                }
+                self.targets(schema, numeric)?
+                    .into_iter()
+                    .map(|(name, dtype)| {
+                        let mut expr = col(&name);
+                        if let Some(v) = self.upper {
+                            let bound = quantile(&name, v, &dtype)?;
+                            expr = when(col(&name).gt(bound.clone()))
+                                .then(bound)
+                                .otherwise(expr);
+                        }
+                        if let Some(v) = self.lower {
+                            let bound = quantile(&name, v, &dtype)?;
+                            expr = when(col(&name).lt(bound.clone()))
+                                .then(bound)
+                                .otherwise(expr);
+                        }
+                        Ok((name, expr))
+                    })
+                    .collect()
+            }
+            CleanMode::FillNull => {
+                let accept = match self.fill {
+                    FillMode::Mean | FillMode::Median => numeric,
+                    _ => any,
+                };
+                self.targets(schema, accept)?
+                    .into_iter()
+                    .map(|(name, dtype)| {
+                        let target = col(&name);
+                        let expr = match self.fill {
+                            FillMode::Constant => match &self.value {
+                                Some(v) => target.fill_null(fill_value(&name, v, &dtype)?),
+                                None => {
+                                    return Err(DataboardError::InvalidQuery(format!(
+                                        "列 {} 未指定填充值",
+                                        name
+                                    )))
+                                }
+                            },
+                            FillMode::Forward => target.forward_fill(None),
+                            FillMode::Backward => target.backward_fill(None),
+                            FillMode::Mean => target.fill_null(col(&name).mean().cast(dtype)),
+                            FillMode::Median => target.fill_null(col(&name).median().cast(dtype)),
+                        };
+                        Ok((name, expr))
+                    })
+                    .collect()
+            }
+            CleanMode::Dedupe => Ok(Vec::default()),
+        }
+    }
+}
+
+// 构建清洗计划, 列不存在或类型不适用时报错
+pub fn plan(steps: &[CleanStep], mut records: LazyFrame) -> Result<CleanPlan, DataboardError> {
+    let schema = records.schema()?;
+    let mut affected = Vec::with_capacity(steps.len());
+    for step in steps {
+        if step.mode == CleanMode::Dedupe {
+            let subset = if step.columns.is_empty() {
+                None
+            } else {
+                for name in step.columns.iter() {
+                    if schema.get(name).is_none() {
+                        return Err(DataboardError::UnknownColumn(name.clone()));
+                    }
+                }
+                Some(step.columns.clone())
+            };
+            let before = records.clone();
+            records = records.unique_stable(subset, UniqueKeepStrategy::First);
+            affected.push((
+                step.mode,
+                Affected::Removed(Box::new(before), Box::new(records.clone())),
+            ));
+            continue;
+        }
+        let exprs = step.exprs(&schema)?;
+        // 值有变化的行, 空值与非空值之间的变化也计入
+        let changed = exprs
+            .iter()
+            .map(|(name, expr)| col(name).neq_missing(expr.clone()))
+            .reduce(|a, b| a.or(b))
+            .unwrap_or(lit(false));
+        let before = records.clone();
+        records = records.with_columns(
+            exprs
+                .into_iter()
+                .map(|(name, expr)| expr.alias(&name))
+                .collect::<Vec<_>>(),
+        );
+        affected.push((step.mode, Affected::Rows(Box::new(before), changed)));
+    }
+    Ok(CleanPlan {
+        records,
+        steps: affected,
+    })
+}
+
+impl CleanPlan {
+    // 统计各步骤影响的行数
+    pub fn count(self, job: &Job) -> Result<Vec<CleanReport>, DataboardError> {
+        let mut ret = Vec::with_capacity(self.steps.len());
+        for (mode, affected) in self.steps {
+            let value = match affected {
+                Affected::Rows(before, changed) => {
+                    let df = job.collect((*before).select([changed.sum().alias("affected")]))?;
+                    let v = df.column("affected")?.cast(&DataType::UInt64)?;
+                    v.u64()?.get(0).unwrap_or(0) as usize
+                }
+                Affected::Removed(before, after) => {
+                    job.count(*before)?.saturating_sub(job.count(*after)?)
+                }
+            };
+            ret.push(CleanReport {
+                mode,
+                affected: value,
+            });
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use polars::export::chrono::NaiveDate;
+
+    fn fill(column: &str, value: &str) -> Result<LazyFrame, DataboardError> {
+        let df = df!(
+            "flag" => [Some(false), None],
+            "count" => [Some(1), None],
+            "day" => [NaiveDate::from_ymd_opt(2024, 1, 1), None],
+            "name" => [Some("a"), None],
+        )
+        .unwrap();
+        let df = df.lazy().with_columns([
+            col("name")
+                .cast(DataType::Categorical(None, Default::default()))
+                .alias("kind"),
+            col("day")
+                .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                .alias("ts"),
+            col("count")
+                .cast(DataType::Decimal(Some(10), Some(2)))
+                .alias("amount"),
+        ]);
+        let step = CleanStep {
+            mode: CleanMode::FillNull,
+            columns: vec![column.to_string()],
+            fill: FillMode::Constant,
+            value: Some(value.to_string()),
+            lower: None,
+            upper: None,
+        };
+        Ok(plan(&[step], df)?.records)
+    }
+
+    fn last(column: &str, value: &str) -> String {
+        let df = fill(column, value).unwrap().collect().unwrap();
+        let v = df.column(column).unwrap().cast(&DataType::String).unwrap();
+        v.str().unwrap().get(1).unwrap().to_string()
+    }
+
+    #[test]
+    fn fill_constant_by_type() {
+        assert_eq!(last("flag", "true"), "true");
+        assert_eq!(last("flag", "1"), "true");
+        assert_eq!(last("count", "1,200"), "1200");
+        assert_eq!(last("day", "2024/02/03"), "2024-02-03");
+        assert_eq!(last("ts", "2024-02-03 04:05:06"), "2024-02-03 04:05:06.000");
+        assert_eq!(last("kind", "b"), "b");
+        assert_eq!(last("amount", "3.5"), "3.50");
+    }
+
+    #[test]
+    fn fill_constant_rejected_in_plan() {
+        for (column, value) in [("flag", "maybe"), ("count", "abc"), ("count", "9999999999")] {
+            assert!(matches!(
+                fill(column, value),
+                Err(DataboardError::InvalidQuery(_))
+            ));
+        }
+    }
+}
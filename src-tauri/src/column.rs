@@ -0,0 +1,216 @@
+use polars::lazy::dsl::{col, concat_str, lit, Expr};
+use polars::prelude::*;
+
+use regex::Regex;
+
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, format};
+
+use crate::error::DataboardError;
+
+// 拆分一列为多列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitColumn {
+    pub column: String,
+    // 生成的列名, 按分隔符拆分时最后一列保留剩余部分
+    pub into: Vec<String>,
+    // 分隔符, 与 pattern 二选一
+    #[serde(default)]
+    pub separator: Option<String>,
+    // 正则, 捕获分组依次对应生成的列
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+// 合并多列为一列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcatColumn {
+    pub columns: Vec<String>,
+    pub target: String,
+    // 连接符, 空值跳过
+    #[serde(default)]
+    pub separator: String,
+}
+
+// 类型转换之后的列操作, 依次为拆分/合并/删除/重命名/排序
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnOperations {
+    #[serde(default)]
+    pub split: Vec<SplitColumn>,
+    #[serde(default)]
+    pub concat: Vec<ConcatColumn>,
+    // 删除的列, 可以是拆分/合并生成的列
+    #[serde(default)]
+    pub drop: Vec<String>,
+    // 原列名 -> 新列名
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+    // 列顺序, 使用重命名后的列名, 未列出的列按原顺序排在后面
+    #[serde(default)]
+    pub order: Vec<String>,
+}
+
+fn require(names: &[String], name: &str) -> Result<(), DataboardError> {
+    if names.iter().any(|v| v == name) {
+        Ok(())
+    } else {
+        Err(DataboardError::UnknownColumn(name.to_string()))
+    }
+}
+
+fn unused(names: &[String], name: &str) -> Result<(), DataboardError> {
+    if name.trim().is_empty() {
+        return Err(DataboardError::InvalidQuery(
+            "生成的列名不能为空".to_string(),
+        ));
+    }
+    if names.iter().any(|v| v == name) {
+        return Err(DataboardError::InvalidQuery(format!("列 {} 已存在", name)));
+    }
+    Ok(())
+}
+
+// 按分隔符拆分后的第 index 段
+fn split_part(s: &Series, separator: &str, index: usize, count: usize) -> PolarsResult<Series> {
+    Ok(s.str()?
+        .into_iter()
+        .map(|op| op.and_then(|v| v.splitn(count, separator).nth(index).map(|v| v.trim())))
+        .collect::<StringChunked>()
+        .into_series())
+}
+
+impl SplitColumn {
+    fn exprs(&self) -> Result<Vec<Expr>, DataboardError> {
+        if self.into.is_empty() {
+            return Err(DataboardError::InvalidQuery(format!(
+                "列 {} 拆分未指定生成的列",
+                self.column
+            )));
+        }
+        let source = col(&self.column).cast(DataType::String);
+        match (&self.separator, &self.pattern) {
+            (Some(separator), None) if !separator.is_empty() => {
+                let count = self.into.len();
+                Ok(self
+                    .into
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        let separator = separator.clone();
+                        source
+                            .clone()
+                            .map(
+                                move |s| split_part(&s, &separator, i, count).map(Some),
+                                GetOutput::from_type(DataType::String),
+                            )
+                            .alias(name)
+                    })
+                    .collect())
+            }
+            (None, Some(pattern)) => {
+                let re = Regex::new(pattern).map_err(|e| {
+                    DataboardError::InvalidQuery(format!("列 {} 拆分正则无效: {}", self.column, e))
+                })?;
+                if re.captures_len() - 1 < self.into.len() {
+                    return Err(DataboardError::InvalidQuery(format!(
+                        "列 {} 拆分正则只有 {} 个捕获分组",
+                        self.column,
+                        re.captures_len() - 1
+                    )));
+                }
+                Ok(self
+                    .into
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        source
+                            .clone()
+                            .str()
+                            .extract(lit(pattern.as_str()), i + 1)
+                            .alias(name)
+                    })
+                    .collect())
+            }
+            _ => Err(DataboardError::InvalidQuery(format!(
+                "列 {} 拆分需指定分隔符或正则之一",
+                self.column
+            ))),
+        }
+    }
+}
+
+impl ColumnOperations {
+    // 在转换后的数据上追加列操作, 列名不存在或冲突时报错
+    pub fn apply(&self, mut lf: LazyFrame) -> Result<LazyFrame, DataboardError> {
+        let mut names: Vec<String> = lf.schema()?.iter_names().map(|v| v.to_string()).collect();
+
+        for split in self.split.iter() {
+            require(&names, &split.column)?;
+            for name in split.into.iter() {
+                unused(&names, name)?;
+                names.push(name.clone());
+            }
+            lf = lf.with_columns(split.exprs()?);
+        }
+        for concat in self.concat.iter() {
+            if concat.columns.is_empty() {
+                return Err(DataboardError::InvalidQuery(format!(
+                    "列 {} 未指定合并的列",
+                    concat.target
+                )));
+            }
+            let mut items = Vec::<Expr>::default();
+            for name in concat.columns.iter() {
+                require(&names, name)?;
+                items.push(col(name).cast(DataType::String));
+            }
+            unused(&names, &concat.target)?;
+            names.push(concat.target.clone());
+            lf = lf.with_column(concat_str(items, &concat.separator, true).alias(&concat.target));
+        }
+
+        for name in self.drop.iter() {
+            require(&names, name)?;
+        }
+        names.retain(|v| !self.drop.contains(v));
+
+        // 原列名与新列名
+        let mut columns: Vec<(String, String)> = names
+            .iter()
+            .map(|v| {
+                (
+                    v.clone(),
+                    self.rename.get(v).unwrap_or(v).trim().to_string(),
+                )
+            })
+            .collect();
+        for name in self.rename.keys() {
+            require(&names, name)?;
+        }
+        for (i, (_, target)) in columns.iter().enumerate() {
+            if target.is_empty() {
+                return Err(DataboardError::InvalidQuery("列名不能为空".to_string()));
+            }
+            if columns[..i].iter().any(|(_, v)| v == target) {
+                return Err(DataboardError::InvalidQuery(format!("列 {} 重复", target)));
+            }
+        }
+
+        let mut ordered = Vec::with_capacity(columns.len());
+        for name in self.order.iter() {
+            match columns.iter().position(|(_, v)| v == name) {
+                Some(i) => ordered.push(columns.remove(i)),
+                None => return Err(DataboardError::UnknownColumn(name.clone())),
+            }
+        }
+        ordered.append(&mut columns);
+
+        Ok(lf.select(
+            ordered
+                .iter()
+                .map(|(name, target)| col(name).alias(target))
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
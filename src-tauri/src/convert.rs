@@ -0,0 +1,818 @@
+use once_cell::sync::Lazy;
+
+use polars::export::chrono::format::{Item, StrftimeItems};
+use polars::export::chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use polars::prelude::*;
+
+use regex::Regex;
+
+use serde::{Deserialize, Serialize};
+
+use std::ops::Range;
+
+use crate::error::DataboardError;
+use crate::job::Job;
+use crate::state::ColumnType;
+
+// 数字, 小数点已统一为 .
+static RE_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"-?\d+(?:\.\d+)?").unwrap());
+// 日期格式按顺序匹配, 年月日依次为捕获分组的序号
+static RE_DATES: Lazy<Vec<(Regex, [usize; 3])>> = Lazy::new(|| {
+    vec![
+        // yyyy-mm-dd yyyy.mm.dd
+        (
+            Regex::new(r"\b(\d{4})[-.](\d{2})[-.](\d{2})\b").unwrap(),
+            [1, 2, 3],
+        ),
+        // yyyymmdd
+        (Regex::new(r"\b(\d{4})(\d{2})(\d{2})\b").unwrap(), [1, 2, 3]),
+        // yymmdd
+        (Regex::new(r"\b(\d{2})(\d{2})(\d{2})\b").unwrap(), [1, 2, 3]),
+        // yyyy/mm/dd
+        (
+            Regex::new(r"\b(\d{4})/(\d{2})/(\d{2})\b").unwrap(),
+            [1, 2, 3],
+        ),
+        // dd/mm/yyyy
+        (
+            Regex::new(r"\b(\d{2})/(\d{2})/(\d{4})\b").unwrap(),
+            [3, 2, 1],
+        ),
+        // yyyy年mm月dd日
+        (
+            Regex::new(r"\b(\d{4})年(\d{2})月(\d{2})日\b").unwrap(),
+            [1, 2, 3],
+        ),
+    ]
+});
+// 日期之后的时间 hh:mm[:ss[.fff]]
+static RE_TIME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\d{1,2}):(\d{2})(?::(\d{2})(?:[.,](\d{1,9}))?)?").unwrap());
+// 完整的日期时间, 仅用于类型推荐
+static RE_DATETIME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\d{4}[-./]\d{2}[-./]\d{2}[ T]\d{1,2}:\d{2}(:\d{2}([.,]\d{1,9})?)?$").unwrap()
+});
+static RE_CLOCK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\d+):(\d{2})(?::(\d{2})(?:\.(\d{1,3}))?)?$").unwrap());
+static RE_DURATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(\d+(?:\.\d+)?)\s*(ms|毫秒|d|天|h|小时|min|m|分钟|分|s|秒)").unwrap()
+});
+
+// 类型推荐读取的行数
+const SUGGEST_SAMPLE: u32 = 1000;
+// 可转换比例达到该值时才推荐为该类型
+const SUGGEST_THRESHOLD: f64 = 0.9;
+
+fn default_decimal() -> char {
+    '.'
+}
+
+fn default_thousands() -> Option<char> {
+    Some(',')
+}
+
+// 单列文本的解析配置, 默认与内置规则一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseOptions {
+    // 日期格式(strftime), 按顺序尝试, 为空时使用内置格式
+    #[serde(default)]
+    pub formats: Vec<String>,
+    // 两位年份小于该值时为20xx, 否则为19xx, 未配置时均为20xx
+    #[serde(default)]
+    pub century_pivot: Option<i32>,
+    // 小数点
+    #[serde(default = "default_decimal")]
+    pub decimal: char,
+    // 千分位分隔符, 为空时没有千分位
+    #[serde(default = "default_thousands")]
+    pub thousands: Option<char>,
+    // 解析前去掉的货币符号, 如 €/$/USD
+    #[serde(default)]
+    pub currency: Vec<String>,
+    // 带 % 的值按百分数处理, 除以100
+    #[serde(default)]
+    pub percent: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            formats: Vec::default(),
+            century_pivot: None,
+            decimal: default_decimal(),
+            thousands: default_thousands(),
+            currency: Vec::default(),
+            percent: false,
+        }
+    }
+}
+
+// 数字文本除以100, 移动小数点以保留精度
+fn shift_percent(val: &str) -> String {
+    let (sign, digits) = match val.strip_prefix('-') {
+        Some(v) => ("-", v),
+        None => ("", val),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let int = format!("{:0>3}", int);
+    let (head, tail) = int.split_at(int.len() - 2);
+    format!("{}{}.{}{}", sign, head, tail, frac)
+}
+
+impl ParseOptions {
+    // 检查配置, 返回错误说明
+    pub fn validate(&self) -> Result<(), String> {
+        if self.thousands == Some(self.decimal) {
+            return Err(format!("千分位分隔符与小数点相同: {}", self.decimal));
+        }
+        if self.decimal.is_ascii_digit() || self.decimal == '-' {
+            return Err(format!("小数点无效: {}", self.decimal));
+        }
+        for fmt in self.formats.iter() {
+            if StrftimeItems::new(fmt).any(|v| v == Item::Error) {
+                return Err(format!("日期格式无效: {}", fmt));
+            }
+        }
+        if let Some(v) = self.century_pivot {
+            if !(0..=100).contains(&v) {
+                return Err(format!("两位年份分界值应在 0~100 之间: {}", v));
+            }
+        }
+        Ok(())
+    }
+
+    // 两位年份补全世纪
+    fn year(&self, yy: i32) -> i32 {
+        match self.century_pivot {
+            Some(pivot) if yy >= pivot => 1900 + yy,
+            _ => 2000 + yy,
+        }
+    }
+
+    // 去掉货币符号和千分位, 小数点统一为 .
+    fn normalize(&self, val: &str) -> String {
+        let mut text = val.to_string();
+        for symbol in self.currency.iter().filter(|v| !v.is_empty()) {
+            text = text.replace(symbol.as_str(), "");
+        }
+        match self.thousands {
+            Some(sep) if sep.is_whitespace() => text.retain(|c| !c.is_whitespace()),
+            Some(sep) => text.retain(|c| c != sep),
+            None => {}
+        }
+        if self.decimal != '.' {
+            text = text.replace(self.decimal, ".");
+        }
+        text
+    }
+
+    // 提取文本中的数字
+    pub fn number(&self, val: &str) -> Option<String> {
+        let text = self.normalize(val);
+        let found = RE_NUMBER.find(&text)?;
+        if self.percent && text[found.end()..].trim_start().starts_with('%') {
+            return Some(shift_percent(found.as_str()));
+        }
+        Some(found.as_str().to_string())
+    }
+
+    // 整个值只有一个数字时返回该数字, 用于类型推荐, 规则与 number 一致
+    fn whole_number(&self, val: &str) -> Option<String> {
+        let text = self.normalize(val);
+        let text = text.trim();
+        let text = text.strip_prefix('+').unwrap_or(text);
+        let found = RE_NUMBER.find(text)?;
+        if found.start() != 0 {
+            return None;
+        }
+        match text[found.end()..].trim_start() {
+            "" => Some(found.as_str().to_string()),
+            "%" if self.percent => Some(shift_percent(found.as_str())),
+            _ => None,
+        }
+    }
+
+    // 整数只取整数部分
+    pub fn int64(&self, val: &str) -> Option<i64> {
+        self.number(val)
+            .and_then(|v| v.split('.').next().and_then(|v| v.parse().ok()))
+    }
+
+    pub fn integer(&self, val: &str) -> Option<i32> {
+        self.int64(val).and_then(|v| i32::try_from(v).ok())
+    }
+
+    pub fn float64(&self, val: &str) -> Option<f64> {
+        self.number(val).and_then(|v| v.parse().ok())
+    }
+
+    pub fn float(&self, val: &str) -> Option<f32> {
+        self.number(val).and_then(|v| v.parse().ok())
+    }
+
+    // 匹配内置日期格式, 返回年月日及匹配的位置, 不校验日期是否有效
+    pub fn date_parts(&self, val: &str) -> Option<(i32, u32, u32, Range<usize>)> {
+        RE_DATES.iter().find_map(|(re, [y, m, d])| {
+            re.captures(val).map(|caps| {
+                let year = caps[*y].parse().unwrap_or(0);
+                (
+                    if caps[*y].len() == 2 {
+                        self.year(year)
+                    } else {
+                        year
+                    },
+                    caps[*m].parse().unwrap_or(0),
+                    caps[*d].parse().unwrap_or(0),
+                    caps.get(0).unwrap().range(),
+                )
+            })
+        })
+    }
+
+    // 按配置的格式依次尝试, 日期格式按零点处理
+    fn parse_formats(&self, val: &str) -> Option<NaiveDateTime> {
+        let val = val.trim();
+        self.formats.iter().find_map(|fmt| {
+            let value = NaiveDateTime::parse_from_str(val, fmt)
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(val, fmt).map(|v| v.and_time(NaiveTime::MIN))
+                })
+                .ok()?;
+            if fmt.contains("%y") {
+                value.with_year(self.year(value.year() % 100))
+            } else {
+                Some(value)
+            }
+        })
+    }
+
+    pub fn date(&self, val: &str) -> Option<NaiveDate> {
+        if !self.formats.is_empty() {
+            return self.parse_formats(val).map(|v| v.date());
+        }
+        self.date_parts(val)
+            .and_then(|(y, m, d, _)| NaiveDate::from_ymd_opt(y, m, d))
+    }
+
+    // 日期加可选的时间, 没有时间时为零点
+    pub fn datetime(&self, val: &str) -> Option<NaiveDateTime> {
+        if !self.formats.is_empty() {
+            return self.parse_formats(val);
+        }
+        let val = val.replace('T', " ");
+        let (y, m, d, range) = self.date_parts(&val)?;
+        let date = NaiveDate::from_ymd_opt(y, m, d)?;
+        let time = match RE_TIME.captures(&val[range.end..]) {
+            Some(caps) => {
+                let second = caps.get(3).map_or(Some(0), |v| v.as_str().parse().ok())?;
+                let nano = caps
+                    .get(4)
+                    .map_or(Some(0), |v| format!("{:0<9}", v.as_str()).parse().ok())?;
+                NaiveTime::from_hms_nano_opt(
+                    caps[1].parse().ok()?,
+                    caps[2].parse().ok()?,
+                    second,
+                    nano,
+                )?
+            }
+            None => NaiveTime::MIN,
+        };
+        Some(date.and_time(time))
+    }
+}
+
+pub fn parse_boolean(val: &str) -> Option<bool> {
+    match val.trim().to_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" | "是" | "真" => Some(true),
+        "false" | "f" | "no" | "n" | "0" | "否" | "假" => Some(false),
+        _ => None,
+    }
+}
+
+// 解析时长为毫秒: 纯数字按秒, h:mm[:ss[.fff]], 或 1d2h30m 这样带单位的写法
+pub fn parse_duration(val: &str) -> Option<i64> {
+    let val = val.trim();
+    if let Ok(v) = val.parse::<f64>() {
+        return finite_millis(v * 1000.0);
+    }
+    if let Some(caps) = RE_CLOCK.captures(val) {
+        let hour: i64 = caps[1].parse().ok()?;
+        let minute: i64 = caps[2].parse().ok()?;
+        let second: i64 = caps.get(3).map_or(Some(0), |v| v.as_str().parse().ok())?;
+        let milli: i64 = caps
+            .get(4)
+            .map_or(Some(0), |v| format!("{:0<3}", v.as_str()).parse().ok())?;
+        return Some(((hour * 60 + minute) * 60 + second) * 1000 + milli);
+    }
+
+    let mut total = 0f64;
+    let mut end = 0;
+    for caps in RE_DURATION.captures_iter(val) {
+        let part = caps.get(0).unwrap();
+        if !val[end..part.start()].trim().is_empty() {
+            return None;
+        }
+        end = part.end();
+        let unit = match &caps[2] {
+            "ms" | "毫秒" => 1.0,
+            "s" | "秒" => 1_000.0,
+            "m" | "min" | "分钟" | "分" => 60_000.0,
+            "h" | "小时" => 3_600_000.0,
+            _ => 86_400_000.0,
+        };
+        total += caps[1].parse::<f64>().ok()? * unit;
+    }
+    if end == 0 || !val[end..].trim().is_empty() {
+        return None;
+    }
+    finite_millis(total)
+}
+
+// nan/inf 及超出范围的毫秒数视为无法转换, 不按饱和值或 0 处理
+fn finite_millis(ms: f64) -> Option<i64> {
+    let ms = ms.round();
+    (ms.is_finite() && ms.abs() < i64::MAX as f64).then_some(ms as i64)
+}
+
+pub fn to_boolean(s: &Series) -> PolarsResult<Series> {
+    match s.dtype() {
+        DataType::String => Ok(s
+            .str()?
+            .into_iter()
+            .map(|op| op.and_then(parse_boolean))
+            .collect::<BooleanChunked>()
+            .into_series()),
+        _ => s.cast(&DataType::Boolean),
+    }
+}
+
+pub fn to_int64(s: &Series, options: &ParseOptions) -> PolarsResult<Series> {
+    match s.dtype() {
+        DataType::String => Ok(s
+            .str()?
+            .into_iter()
+            .map(|op| op.and_then(|v| options.int64(v)))
+            .collect::<Int64Chunked>()
+            .into_series()),
+        _ => s.cast(&DataType::Int64),
+    }
+}
+
+pub fn to_float64(s: &Series, options: &ParseOptions) -> PolarsResult<Series> {
+    match s.dtype() {
+        DataType::String => Ok(s
+            .str()?
+            .into_iter()
+            .map(|op| op.and_then(|v| options.float64(v)))
+            .collect::<Float64Chunked>()
+            .into_series()),
+        _ => s.cast(&DataType::Float64),
+    }
+}
+
+// 文本按数字提取后转换, 避免经过浮点数损失精度
+pub fn to_decimal(
+    s: &Series,
+    precision: Option<usize>,
+    scale: usize,
+    options: &ParseOptions,
+) -> PolarsResult<Series> {
+    let dtype = DataType::Decimal(precision, Some(scale));
+    match s.dtype() {
+        DataType::String => s
+            .str()?
+            .into_iter()
+            .map(|op| op.and_then(|v| options.number(v)))
+            .collect::<StringChunked>()
+            .into_series()
+            .cast(&dtype),
+        _ => s.cast(&dtype),
+    }
+}
+
+// 数值按 unix 秒处理, 无时区的值按指定时区的当地时间处理
+pub fn to_datetime(
+    s: &Series,
+    unit: TimeUnit,
+    tz: Option<&str>,
+    options: &ParseOptions,
+) -> PolarsResult<Series> {
+    let naive = match s.dtype() {
+        DataType::String => {
+            let values: Vec<Option<NaiveDateTime>> = s
+                .str()?
+                .into_iter()
+                .map(|op| op.and_then(|v| options.datetime(v)))
+                .collect();
+            Series::new(s.name(), values).cast(&DataType::Datetime(unit, None))?
+        }
+        // 已有时区的换算到指定时区
+        DataType::Datetime(_, Some(_)) => {
+            return s.cast(&DataType::Datetime(unit, tz.map(|v| v.to_string())))
+        }
+        dtype if dtype.is_numeric() => (s.cast(&DataType::Float64)? * 1000.0)
+            .cast(&DataType::Int64)?
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?
+            .cast(&DataType::Datetime(unit, None))?,
+        _ => s.cast(&DataType::Datetime(unit, None))?,
+    };
+    match tz {
+        // 夏令时重复的时间取较早的一个, 不存在的时间置空
+        Some(tz) => Ok(replace_time_zone(
+            naive.datetime()?,
+            Some(tz),
+            &StringChunked::from_iter([Some("earliest")]),
+            NonExistent::Null,
+        )?
+        .into_series()),
+        None => Ok(naive),
+    }
+}
+
+// 数值按秒处理
+pub fn to_duration(s: &Series, unit: TimeUnit) -> PolarsResult<Series> {
+    let millis = match s.dtype() {
+        DataType::String => s
+            .str()?
+            .into_iter()
+            .map(|op| op.and_then(parse_duration))
+            .collect::<Int64Chunked>()
+            .into_series(),
+        DataType::Duration(_) => return s.cast(&DataType::Duration(unit)),
+        _ => (s.cast(&DataType::Float64)? * 1000.0).cast(&DataType::Int64)?,
+    };
+    millis
+        .cast(&DataType::Duration(TimeUnit::Milliseconds))?
+        .cast(&DataType::Duration(unit))
+}
+
+pub fn to_categorical(s: &Series) -> PolarsResult<Series> {
+    let text = match s.dtype() {
+        DataType::String => s
+            .str()?
+            .into_iter()
+            .map(|op| op.map(|val| val.trim()))
+            .collect::<StringChunked>()
+            .into_series(),
+        _ => s.cast(&DataType::String)?,
+    };
+    text.cast(&DataType::Categorical(None, Default::default()))
+}
+
+// 转为JSON输出支持的类型: 定点小数转为文本以保留精度, 带时区的日期时间转为当地时间
+pub fn json_compatible(s: &Series) -> PolarsResult<Series> {
+    match s.dtype() {
+        DataType::Decimal(_, _) => s.cast(&DataType::String),
+        DataType::Datetime(_, Some(_)) => Ok(replace_time_zone(
+            s.datetime()?,
+            None,
+            &StringChunked::from_iter([Some("raise")]),
+            NonExistent::Raise,
+        )?
+        .into_series()),
+        _ => Ok(s.clone()),
+    }
+}
+
+pub fn json_frame(df: &DataFrame) -> PolarsResult<DataFrame> {
+    DataFrame::new(
+        df.get_columns()
+            .iter()
+            .map(json_compatible)
+            .collect::<PolarsResult<Vec<_>>>()?,
+    )
+}
+
+// CSV 不支持写入时长和定点小数, 按文本写出
+pub fn csv_frame(df: &DataFrame) -> PolarsResult<DataFrame> {
+    DataFrame::new(
+        df.get_columns()
+            .iter()
+            .map(|s| match s.dtype() {
+                DataType::Duration(_) | DataType::Decimal(_, _) => s.cast(&DataType::String),
+                _ => Ok(s.clone()),
+            })
+            .collect::<PolarsResult<Vec<_>>>()?,
+    )
+}
+
+#[derive(Serialize)]
+pub struct TypeSuggestion {
+    pub name: String,
+    pub column_type: ColumnType,
+    // 完整符合该类型格式的值占比
+    pub confidence: f64,
+    // 按该类型转换时失败的值占比
+    pub failed: f64,
+    // 参与推断的非空值数量
+    pub samples: usize,
+}
+
+// 按原始列类型直接推荐, 超出32位范围的整数推荐为64位
+fn dtype_suggestion(s: &Series) -> Result<ColumnType, DataboardError> {
+    Ok(match s.dtype() {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            ColumnType::Integer
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => {
+            let min = s.min::<i64>()?.unwrap_or(0);
+            let max = s.max::<i64>()?.unwrap_or(0);
+            if min >= i32::MIN as i64 && max <= i32::MAX as i64 {
+                ColumnType::Integer
+            } else {
+                ColumnType::Int64
+            }
+        }
+        DataType::Float32 | DataType::Float64 => ColumnType::Float,
+        DataType::Boolean => ColumnType::Boolean,
+        DataType::Date => ColumnType::Date,
+        DataType::Datetime(unit, tz) => ColumnType::Datetime(*unit, tz.clone()),
+        DataType::Duration(unit) => ColumnType::Duration(*unit),
+        DataType::Decimal(precision, scale) => ColumnType::Decimal(*precision, scale.unwrap_or(2)),
+        DataType::Categorical(_, _) => ColumnType::Categorical,
+        _ => ColumnType::String,
+    })
+}
+
+// 文本列按转换规则推断类型
+fn suggest_column(s: &Series) -> Result<TypeSuggestion, DataboardError> {
+    let name = s.name().to_string();
+    if s.dtype() != &DataType::String {
+        return Ok(TypeSuggestion {
+            name,
+            column_type: dtype_suggestion(s)?,
+            confidence: 1.0,
+            failed: 0.0,
+            samples: s.len() - s.null_count(),
+        });
+    }
+
+    let values: Vec<&str> = s.str()?.into_iter().flatten().map(|v| v.trim()).collect();
+    if values.is_empty() {
+        return Ok(TypeSuggestion {
+            name,
+            column_type: ColumnType::String,
+            confidence: 0.0,
+            failed: 0.0,
+            samples: 0,
+        });
+    }
+
+    let options = ParseOptions::default();
+    let total = values.len() as f64;
+    let share = |f: &dyn Fn(&str) -> bool| values.iter().filter(|v| f(v)).count() as f64 / total;
+    let is_date = |v: &str| match options.date_parts(v) {
+        Some((y, m, d, range)) => {
+            range.len() == v.len() && NaiveDate::from_ymd_opt(y, m, d).is_some()
+        }
+        None => false,
+    };
+    // 日期时间列中可以有只写日期的值
+    let is_datetime =
+        |v: &str| is_date(v) || RE_DATETIME.is_match(v) && options.datetime(v).is_some();
+
+    // 数字按数据配置的转换规则识别, 不接受 nan/inf 等文本
+    let is_int32 = |v: &str| {
+        options
+            .whole_number(v)
+            .is_some_and(|n| !n.contains('.') && n.parse::<i32>().is_ok())
+    };
+    let is_int64 = |v: &str| {
+        options
+            .whole_number(v)
+            .is_some_and(|n| !n.contains('.') && n.parse::<i64>().is_ok())
+    };
+    let is_float = |v: &str| options.whole_number(v).is_some();
+    let is_boolean = |v: &str| parse_boolean(v).is_some();
+    let is_duration = |v: &str| parse_duration(v).is_some();
+    // 完整符合格式的比例最高者优先, 比例相同时取数组中靠前的类型,
+    // 如只有 0/1 的列 Integer 与 Boolean 比例相同, 推荐为 Integer
+    let candidates = [
+        (ColumnType::Integer, &is_int32 as &dyn Fn(&str) -> bool),
+        (ColumnType::Int64, &is_int64),
+        (ColumnType::Float, &is_float),
+        (ColumnType::Boolean, &is_boolean),
+        (ColumnType::Date, &is_date),
+        (
+            ColumnType::Datetime(TimeUnit::Milliseconds, None),
+            &is_datetime,
+        ),
+        (ColumnType::Duration(TimeUnit::Milliseconds), &is_duration),
+    ];
+    let (best, confidence) = candidates
+        .iter()
+        .map(|(column_type, f)| (column_type, share(*f)))
+        .fold(None::<(&ColumnType, f64)>, |best, item| match best {
+            Some((_, b)) if b >= item.1 => best,
+            _ => Some(item),
+        })
+        .unwrap();
+    let best = best.clone();
+
+    if confidence < SUGGEST_THRESHOLD {
+        // 文本类型的占比为不符合任何候选类型格式的值
+        return Ok(TypeSuggestion {
+            name,
+            column_type: ColumnType::String,
+            confidence: share(&|v| !candidates.iter().any(|(_, f)| f(v))),
+            failed: 0.0,
+            samples: values.len(),
+        });
+    }
+    let failed = match best {
+        ColumnType::Integer => share(&|v| options.integer(v).is_none()),
+        ColumnType::Int64 => share(&|v| options.int64(v).is_none()),
+        ColumnType::Float => share(&|v| options.float(v).is_none()),
+        ColumnType::Boolean => share(&|v| parse_boolean(v).is_none()),
+        ColumnType::Date => share(&|v| options.date(v).is_none()),
+        ColumnType::Duration(_) => share(&|v| parse_duration(v).is_none()),
+        _ => share(&|v| options.datetime(v).is_none()),
+    };
+    Ok(TypeSuggestion {
+        name,
+        column_type: best,
+        confidence,
+        failed,
+        samples: values.len(),
+    })
+}
+
+// 抽取前若干行推荐各列类型
+pub fn suggest_types(records: LazyFrame, job: &Job) -> Result<Vec<TypeSuggestion>, DataboardError> {
+    let df = job.collect(records.limit(SUGGEST_SAMPLE))?;
+    df.get_columns().iter().map(suggest_column).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn european() -> ParseOptions {
+        ParseOptions {
+            decimal: ',',
+            thousands: Some('.'),
+            currency: vec!["€".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn decimal_comma_and_thousands() {
+        let options = european();
+        assert_eq!(options.number("1.234,56 €").as_deref(), Some("1234.56"));
+        assert_eq!(options.float64("-1.234.567,5"), Some(-1234567.5));
+        assert_eq!(options.int64("€ 12.345,99"), Some(12345));
+        assert_eq!(options.number("n/a"), None);
+
+        let default = ParseOptions::default();
+        assert_eq!(default.float64("1,234.5"), Some(1234.5));
+        let options = ParseOptions {
+            decimal: ',',
+            thousands: Some(' '),
+            ..Default::default()
+        };
+        assert_eq!(options.float64("1 234,5"), Some(1234.5));
+        let options = ParseOptions {
+            thousands: None,
+            ..Default::default()
+        };
+        assert_eq!(options.int64("1,234"), Some(1));
+        assert_eq!(default.integer("3000000000"), None);
+        assert_eq!(default.int64("3000000000"), Some(3000000000));
+    }
+
+    #[test]
+    fn percent_shifts_decimal_point() {
+        assert_eq!(shift_percent("5"), "0.05");
+        assert_eq!(shift_percent("1234"), "12.34");
+        assert_eq!(shift_percent("-12.5"), "-0.125");
+        assert_eq!(shift_percent("0.5"), "0.005");
+
+        let options = ParseOptions {
+            percent: true,
+            ..european()
+        };
+        assert_eq!(options.number("12,5 %").as_deref(), Some("0.125"));
+        assert_eq!(options.number("12,5").as_deref(), Some("12.5"));
+        assert_eq!(european().number("12,5%").as_deref(), Some("12.5"));
+    }
+
+    #[test]
+    fn century_pivot() {
+        let default = ParseOptions::default();
+        assert_eq!(default.date("991231"), date(2099, 12, 31));
+        let options = ParseOptions {
+            century_pivot: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(options.date("491231"), date(2049, 12, 31));
+        assert_eq!(options.date("500101"), date(1950, 1, 1));
+        // 四位年份不受影响
+        assert_eq!(options.date("1999-01-02"), date(1999, 1, 2));
+
+        let options = ParseOptions {
+            formats: vec!["%d/%m/%y".to_string()],
+            century_pivot: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(options.date("01/02/29"), date(2029, 2, 1));
+        assert_eq!(options.date("01/02/30"), date(1930, 2, 1));
+    }
+
+    #[test]
+    fn builtin_and_explicit_formats() {
+        let default = ParseOptions::default();
+        assert_eq!(default.date("2024年03月05日"), date(2024, 3, 5));
+        assert_eq!(default.date("05/03/2024"), date(2024, 3, 5));
+        assert_eq!(default.date("2024-02-30"), None);
+        assert_eq!(
+            default.datetime("2024-03-05T08:09:10.5"),
+            date(2024, 3, 5).and_then(|v| v.and_hms_milli_opt(8, 9, 10, 500))
+        );
+        assert_eq!(
+            default.datetime("2024-03-05"),
+            date(2024, 3, 5).map(|v| v.and_time(NaiveTime::MIN))
+        );
+
+        // 配置格式后不再使用内置格式, 日期格式也可解析日期时间
+        let options = ParseOptions {
+            formats: vec!["%m/%d/%Y".to_string(), "%Y%m%d %H%M".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(options.date("03/05/2024"), date(2024, 3, 5));
+        assert_eq!(options.date("2024-03-05"), None);
+        assert_eq!(
+            options.datetime("20240305 0809"),
+            date(2024, 3, 5).and_then(|v| v.and_hms_opt(8, 9, 0))
+        );
+    }
+
+    #[test]
+    fn validate_options() {
+        assert!(ParseOptions::default().validate().is_ok());
+        assert!(european().validate().is_ok());
+        let invalid = [
+            ParseOptions {
+                decimal: ',',
+                ..Default::default()
+            },
+            ParseOptions {
+                decimal: '1',
+                ..Default::default()
+            },
+            ParseOptions {
+                formats: vec!["%Q".to_string()],
+                ..Default::default()
+            },
+            ParseOptions {
+                century_pivot: Some(101),
+                ..Default::default()
+            },
+        ];
+        for options in invalid {
+            assert!(options.validate().is_err(), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn suggest_tie_and_text_share() {
+        // 0/1 同时符合整数与布尔, 取靠前的整数
+        let s = Series::new("flag", ["0", "1", "1", "0"]);
+        let suggestion = suggest_column(&s).unwrap();
+        assert_eq!(suggestion.column_type, ColumnType::Integer);
+        assert_eq!(suggestion.confidence, 1.0);
+
+        // 文本的占比为不符合任何类型的值
+        let s = Series::new("name", ["apple", "1", "2024-01-05", "pear", "kiwi"]);
+        let suggestion = suggest_column(&s).unwrap();
+        assert_eq!(suggestion.column_type, ColumnType::String);
+        assert!((suggestion.confidence - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn suggest_numbers_like_etl() {
+        // nan/inf 可以被 f64 解析, 但数据配置的转换不会按数字处理
+        let s = Series::new("value", ["nan", "inf", "infinity", "NaN", "1.5"]);
+        let suggestion = suggest_column(&s).unwrap();
+        assert_eq!(suggestion.column_type, ColumnType::String);
+        assert!((suggestion.confidence - 0.8).abs() < 1e-9);
+
+        let s = Series::new("value", ["1,234", "+5", "-12"]);
+        assert_eq!(suggest_column(&s).unwrap().column_type, ColumnType::Integer);
+        let s = Series::new("value", ["1,234.5", "5", " 3.25 "]);
+        assert_eq!(suggest_column(&s).unwrap().column_type, ColumnType::Float);
+    }
+
+    #[test]
+    fn duration_rejects_non_finite() {
+        assert_eq!(parse_duration("1.5"), Some(1500));
+        assert_eq!(parse_duration("1h30m"), Some(5_400_000));
+        for v in ["nan", "NaN", "inf", "-infinity", "1e300"] {
+            assert_eq!(parse_duration(v), None, "{}", v);
+        }
+    }
+}
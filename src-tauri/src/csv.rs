@@ -0,0 +1,308 @@
+use encoding_rs::{Encoding, GB18030, UTF_8};
+
+use polars::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    format,
+    io::{Cursor, Read as _},
+};
+
+use crate::error::DataboardError;
+
+fn default_encoding() -> String {
+    "auto".to_string()
+}
+fn default_quote_char() -> Option<char> {
+    Some('"')
+}
+fn default_true() -> bool {
+    true
+}
+fn default_infer_schema_length() -> Option<usize> {
+    Some(100)
+}
+
+// 扫描文件时用于识别编码和分隔符的文件头长度
+const SNIFF_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CsvOptions {
+    // 分隔符, 为空时根据首行自动识别
+    #[serde(default)]
+    pub separator: Option<char>,
+    // 文件编码, auto 时非UTF-8文件按GB18030(兼容GBK)处理
+    #[serde(default = "default_encoding")]
+    pub encoding: String,
+    // 引号字符, 为空时不处理引号
+    #[serde(default = "default_quote_char")]
+    pub quote_char: Option<char>,
+    // 首行(跳过后)是否为表头
+    #[serde(default = "default_true")]
+    pub has_header: bool,
+    // 表头之前跳过的行数
+    #[serde(default)]
+    pub skip_rows: usize,
+    // 注释行前缀
+    #[serde(default)]
+    pub comment_prefix: Option<String>,
+    // 视为空值的字符串
+    #[serde(default)]
+    pub null_values: Vec<String>,
+    // 类型推断读取的行数, 为空时读取全部
+    #[serde(default = "default_infer_schema_length")]
+    pub infer_schema_length: Option<usize>,
+}
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            separator: None,
+            encoding: default_encoding(),
+            quote_char: default_quote_char(),
+            has_header: true,
+            skip_rows: 0,
+            comment_prefix: None,
+            null_values: Vec::new(),
+            infer_schema_length: default_infer_schema_length(),
+        }
+    }
+}
+
+fn ascii_byte(value: char, name: &str) -> Result<u8, DataboardError> {
+    if value.is_ascii() {
+        Ok(value as u8)
+    } else {
+        Err(DataboardError::InvalidQuery(format!(
+            "{} 只支持单字节字符, 当前为 {}",
+            name, value
+        )))
+    }
+}
+
+fn auto_encoding(encoding: &str) -> bool {
+    let label = encoding.trim().to_lowercase();
+    label.is_empty() || label == "auto"
+}
+
+// 识别文件编码, bytes 只是文件开头时(complete 为 false)末尾截断的字符不影响判断
+fn detect_encoding(
+    bytes: &[u8],
+    encoding: &str,
+    complete: bool,
+) -> Result<&'static Encoding, DataboardError> {
+    let label = encoding.trim().to_lowercase();
+    if auto_encoding(&label) {
+        return Ok(match Encoding::for_bom(bytes) {
+            Some((v, _)) => v,
+            None => match std::str::from_utf8(bytes) {
+                Ok(_) => UTF_8,
+                Err(e) if !complete && e.error_len().is_none() => UTF_8,
+                Err(_) => GB18030,
+            },
+        });
+    }
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| DataboardError::InvalidQuery(format!("不支持的编码 {}", encoding)))
+}
+
+// 逐块检查整个文件是否为UTF-8, 跨块截断的字符留到下一块判断
+fn utf8_file(path: &str) -> Result<bool, DataboardError> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; SNIFF_SIZE];
+    let mut pending = 0;
+    loop {
+        let n = file.read(&mut buf[pending..])?;
+        if n == 0 {
+            return Ok(pending == 0);
+        }
+        let len = pending + n;
+        match std::str::from_utf8(&buf[..len]) {
+            Ok(_) => pending = 0,
+            Err(e) if e.error_len().is_none() => {
+                let valid = e.valid_up_to();
+                buf.copy_within(valid..len, 0);
+                pending = len - valid;
+            }
+            Err(_) => return Ok(false),
+        }
+    }
+}
+
+// 按配置的编码转换为UTF-8
+fn decode(bytes: Vec<u8>, encoding: &str) -> Result<Vec<u8>, DataboardError> {
+    let encoding = detect_encoding(&bytes, encoding, true)?;
+    log::debug!("csv encoding {}", encoding.name());
+
+    if encoding == UTF_8 {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Ok(bytes[3..].to_vec());
+        }
+        return Ok(bytes);
+    }
+    let (text, _, malformed) = encoding.decode(&bytes);
+    if malformed {
+        log::warn!("csv contains invalid {} sequences", encoding.name());
+    }
+    Ok(text.into_owned().into_bytes())
+}
+
+// 根据首个非注释行识别分隔符
+fn detect_separator(bytes: &[u8], options: &CsvOptions) -> u8 {
+    let line = bytes
+        .split(|b| *b == b'\n')
+        .skip(options.skip_rows)
+        .find(|line| match &options.comment_prefix {
+            Some(prefix) if !prefix.is_empty() => !line.starts_with(prefix.as_bytes()),
+            _ => true,
+        })
+        .unwrap_or_default();
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .map(|sep| (sep, line.iter().filter(|b| **b == sep).count()))
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .map(|(sep, _)| sep)
+        .unwrap_or(b',')
+}
+
+// 分隔符和引号字符
+fn separator_and_quote(
+    bytes: &[u8],
+    options: &CsvOptions,
+) -> Result<(u8, Option<u8>), DataboardError> {
+    let separator = match options.separator {
+        Some(v) => ascii_byte(v, "分隔符")?,
+        None => detect_separator(bytes, options),
+    };
+    let quote_char = match options.quote_char {
+        Some(v) => Some(ascii_byte(v, "引号字符")?),
+        None => None,
+    };
+    Ok((separator, quote_char))
+}
+
+fn comment_prefix(options: &CsvOptions) -> Option<&str> {
+    options
+        .comment_prefix
+        .as_ref()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.as_str())
+}
+
+fn null_values(options: &CsvOptions) -> Option<NullValues> {
+    if options.null_values.is_empty() {
+        None
+    } else {
+        Some(NullValues::AllColumns(options.null_values.clone()))
+    }
+}
+
+// 扫描CSV文件, 查询时按需读取; 非UTF-8编码的文件需要先整体转换后读入内存
+pub fn scan_csv(path: &str, options: &CsvOptions) -> Result<LazyFrame, DataboardError> {
+    let mut head = Vec::with_capacity(SNIFF_SIZE);
+    std::fs::File::open(path)?
+        .take(SNIFF_SIZE as u64)
+        .read_to_end(&mut head)?;
+    let complete = head.len() < SNIFF_SIZE;
+    let encoding = detect_encoding(&head, &options.encoding, complete)?;
+    // 文件头为UTF-8时仍需检查全部内容, 否则后面出现的GBK字符会在查询时才报错
+    let utf8 = encoding == UTF_8
+        && (complete
+            || !auto_encoding(&options.encoding)
+            || Encoding::for_bom(&head).is_some()
+            || utf8_file(path)?);
+    if !utf8 {
+        return Ok(read_csv(path, options)?.lazy());
+    }
+
+    let (separator, quote_char) = separator_and_quote(&head, options)?;
+    let lf = LazyCsvReader::new(path)
+        .with_glob(false)
+        .with_has_header(options.has_header)
+        .with_skip_rows(options.skip_rows)
+        .with_infer_schema_length(options.infer_schema_length)
+        .with_separator(separator)
+        .with_quote_char(quote_char)
+        .with_comment_prefix(comment_prefix(options))
+        .with_null_values(null_values(options))
+        .finish()
+        .map_err(DataboardError::parse)?;
+    Ok(lf)
+}
+
+// 读取CSV文本
+pub fn read_csv(path: &str, options: &CsvOptions) -> Result<DataFrame, DataboardError> {
+    let bytes = decode(std::fs::read(path)?, &options.encoding)?;
+
+    let (separator, quote_char) = separator_and_quote(&bytes, options)?;
+    let comment_prefix = comment_prefix(options);
+    let null_values = null_values(options);
+
+    let df = CsvReadOptions::default()
+        .with_has_header(options.has_header)
+        .with_skip_rows(options.skip_rows)
+        .with_infer_schema_length(options.infer_schema_length)
+        .map_parse_options(|parse| {
+            parse
+                .with_separator(separator)
+                .with_quote_char(quote_char)
+                .with_comment_prefix(comment_prefix)
+                .with_null_values(null_values.clone())
+        })
+        .into_reader_with_file_handle(Cursor::new(bytes))
+        .finish()
+        .map_err(DataboardError::parse)?;
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write as _;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn scan_gbk_after_ascii_head() {
+        // 文件头超过识别长度且全为ASCII, 最后一行为GBK编码的 "中文"
+        let mut bytes = b"id,name\n".to_vec();
+        for i in 0..8000 {
+            bytes.extend_from_slice(format!("{},name{}\n", i, i).as_bytes());
+        }
+        assert!(bytes.len() > SNIFF_SIZE);
+        bytes.extend_from_slice(b"8000,\xD6\xD0\xCE\xC4\n");
+        let path = temp_file("databoard_gbk_tail.csv", &bytes);
+
+        let df = scan_csv(&path, &CsvOptions::default())
+            .unwrap()
+            .tail(1)
+            .collect()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            df.column("name").unwrap().str().unwrap().get(0),
+            Some("中文")
+        );
+    }
+
+    #[test]
+    fn utf8_char_across_chunks() {
+        // 多字节字符跨越读取块的边界
+        let mut bytes = b"id,name\n".to_vec();
+        bytes.resize(SNIFF_SIZE - 1, b'a');
+        bytes.extend_from_slice("中文\n".as_bytes());
+        let path = temp_file("databoard_utf8_chunks.csv", &bytes);
+        let utf8 = utf8_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(utf8);
+    }
+}
@@ -0,0 +1,163 @@
+use polars::prelude::PolarsError;
+
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use std::fmt;
+
+use crate::formula::FormulaError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataboardError {
+    // 文件读写错误
+    Io(String),
+    // 文件内容或计算规则解析错误
+    Parse(String),
+    // 列不存在
+    UnknownColumn(String),
+    // 列类型与操作不匹配
+    TypeMismatch(String),
+    // 查询或配置不合法
+    InvalidQuery(String),
+    // 任务已取消
+    Cancelled(String),
+}
+impl DataboardError {
+    // 错误编码, 供前端区分处理
+    pub fn code(&self) -> &'static str {
+        match self {
+            DataboardError::Io(_) => "io",
+            DataboardError::Parse(_) => "parse",
+            DataboardError::UnknownColumn(_) => "unknown_column",
+            DataboardError::TypeMismatch(_) => "type_mismatch",
+            DataboardError::InvalidQuery(_) => "invalid_query",
+            DataboardError::Cancelled(_) => "cancelled",
+        }
+    }
+
+    // 错误提示
+    pub fn message(&self) -> &'static str {
+        match self {
+            DataboardError::Io(_) => "文件读写失败",
+            DataboardError::Parse(_) => "数据解析失败",
+            DataboardError::UnknownColumn(_) => "列不存在",
+            DataboardError::TypeMismatch(_) => "列类型不匹配",
+            DataboardError::InvalidQuery(_) => "查询配置错误",
+            DataboardError::Cancelled(_) => "任务已取消",
+        }
+    }
+
+    // 错误详情
+    pub fn detail(&self) -> &str {
+        match self {
+            DataboardError::Io(v)
+            | DataboardError::Parse(v)
+            | DataboardError::UnknownColumn(v)
+            | DataboardError::TypeMismatch(v)
+            | DataboardError::InvalidQuery(v)
+            | DataboardError::Cancelled(v) => v.as_str(),
+        }
+    }
+
+    // 在详情前附加出错的位置, 如文件名
+    pub fn context(self, prefix: &str) -> DataboardError {
+        let detail = format!("{}: {}", prefix, self.detail());
+        match self {
+            DataboardError::Io(_) => DataboardError::Io(detail),
+            DataboardError::Parse(_) => DataboardError::Parse(detail),
+            DataboardError::UnknownColumn(_) => DataboardError::UnknownColumn(detail),
+            DataboardError::TypeMismatch(_) => DataboardError::TypeMismatch(detail),
+            DataboardError::InvalidQuery(_) => DataboardError::InvalidQuery(detail),
+            DataboardError::Cancelled(_) => DataboardError::Cancelled(detail),
+        }
+    }
+
+    // 读取文件内容时的错误都视为解析错误, IO错误除外
+    pub fn parse(e: PolarsError) -> DataboardError {
+        let mut kind = &e;
+        while let PolarsError::Context { error, .. } = kind {
+            kind = error;
+        }
+        match kind {
+            PolarsError::IO { .. } => DataboardError::from(e),
+            _ => DataboardError::Parse(e.to_string()),
+        }
+    }
+}
+impl fmt::Display for DataboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.message(), self.detail())
+    }
+}
+impl std::error::Error for DataboardError {}
+
+impl Serialize for DataboardError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("DataboardError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("detail", self.detail())?;
+        state.end()
+    }
+}
+
+impl From<PolarsError> for DataboardError {
+    fn from(e: PolarsError) -> Self {
+        let detail = e.to_string();
+        let mut kind = &e;
+        while let PolarsError::Context { error, .. } = kind {
+            kind = error;
+        }
+        match kind {
+            PolarsError::IO { .. } => DataboardError::Io(detail),
+            PolarsError::ColumnNotFound(_)
+            | PolarsError::SchemaFieldNotFound(_)
+            | PolarsError::StructFieldNotFound(_) => DataboardError::UnknownColumn(detail),
+            PolarsError::InvalidOperation(_) | PolarsError::SchemaMismatch(_) => {
+                DataboardError::TypeMismatch(detail)
+            }
+            _ => DataboardError::InvalidQuery(detail),
+        }
+    }
+}
+
+impl From<std::io::Error> for DataboardError {
+    fn from(e: std::io::Error) -> Self {
+        DataboardError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for DataboardError {
+    fn from(e: serde_json::Error) -> Self {
+        DataboardError::Parse(e.to_string())
+    }
+}
+
+impl From<glob::PatternError> for DataboardError {
+    fn from(e: glob::PatternError) -> Self {
+        DataboardError::InvalidQuery(e.to_string())
+    }
+}
+
+impl From<glob::GlobError> for DataboardError {
+    fn from(e: glob::GlobError) -> Self {
+        DataboardError::Io(e.to_string())
+    }
+}
+
+impl From<calamine::Error> for DataboardError {
+    fn from(e: calamine::Error) -> Self {
+        match e {
+            calamine::Error::Io(e) => DataboardError::Io(e.to_string()),
+            _ => DataboardError::Parse(e.to_string()),
+        }
+    }
+}
+
+impl From<FormulaError> for DataboardError {
+    fn from(e: FormulaError) -> Self {
+        DataboardError::Parse(e.to_string())
+    }
+}
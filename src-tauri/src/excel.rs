@@ -0,0 +1,187 @@
+use calamine::{open_workbook_auto, Data, DataType as _, Reader};
+
+use polars::export::chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use polars::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashSet, format};
+
+use crate::error::DataboardError;
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExcelOptions {
+    // 工作表名称, 为空时读取第一个工作表
+    #[serde(default)]
+    pub sheet: Option<String>,
+    // 首行(跳过后)是否为表头
+    #[serde(default = "default_true")]
+    pub has_header: bool,
+    // 表头之前跳过的行数
+    #[serde(default)]
+    pub skip_rows: usize,
+}
+impl Default for ExcelOptions {
+    fn default() -> Self {
+        ExcelOptions {
+            sheet: None,
+            has_header: true,
+            skip_rows: 0,
+        }
+    }
+}
+
+// 工作簿中的工作表名称
+pub fn sheet_names(path: &str) -> Result<Vec<String>, DataboardError> {
+    let workbook = open_workbook_auto(path)?;
+    Ok(workbook.sheet_names())
+}
+
+// 读取工作表为DataFrame
+pub fn read_excel(path: &str, options: &ExcelOptions) -> Result<DataFrame, DataboardError> {
+    let mut workbook = open_workbook_auto(path)?;
+    let sheet = match &options.sheet {
+        Some(v) if !v.is_empty() => v.clone(),
+        _ => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| DataboardError::Parse("工作簿中没有工作表".to_string()))?,
+    };
+    let range = workbook.worksheet_range(&sheet)?;
+
+    let mut rows = range
+        .rows()
+        .skip(options.skip_rows)
+        .filter(|row| row.iter().any(|c| !matches!(c, Data::Empty)));
+    let width = range.width();
+
+    let header: Vec<String> = if options.has_header {
+        match rows.next() {
+            Some(row) => row
+                .iter()
+                .map(|c| c.to_string().trim().to_string())
+                .collect(),
+            None => Vec::new(),
+        }
+    } else {
+        Vec::new()
+    };
+    let names = column_names(&header, width);
+
+    let mut cells: Vec<Vec<&Data>> = vec![Vec::new(); width];
+    for row in rows {
+        for (i, column) in cells.iter_mut().enumerate() {
+            column.push(row.get(i).unwrap_or(&Data::Empty));
+        }
+    }
+
+    let columns: Vec<Series> = names
+        .iter()
+        .zip(cells.iter())
+        .map(|(name, values)| to_series(name, values))
+        .collect::<Result<_, _>>()?;
+    Ok(DataFrame::new(columns)?)
+}
+
+// 生成列名, 空表头以 column_序号 命名, 重名追加序号
+fn column_names(header: &[String], width: usize) -> Vec<String> {
+    let mut used = HashSet::new();
+    (0..width)
+        .map(|i| {
+            let base = match header.get(i) {
+                Some(v) if !v.is_empty() => v.clone(),
+                _ => format!("column_{}", i + 1),
+            };
+            let mut name = base.clone();
+            let mut n = 1;
+            while !used.insert(name.clone()) {
+                n += 1;
+                name = format!("{}_{}", base, n);
+            }
+            name
+        })
+        .collect()
+}
+
+fn is_blank(c: &Data) -> bool {
+    matches!(c, Data::Empty | Data::Error(_))
+}
+
+fn as_datetime(c: &Data) -> Option<NaiveDateTime> {
+    match c {
+        Data::DateTime(_) => c.as_datetime(),
+        Data::DateTimeIso(v) => v.parse::<NaiveDateTime>().ok().or_else(|| {
+            v.parse::<NaiveDate>()
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        }),
+        _ => None,
+    }
+}
+
+// 按单元格类型推断列类型: 整数/小数/布尔/日期/日期时间, 混合类型按字符串处理
+fn to_series(name: &str, values: &[&Data]) -> Result<Series, DataboardError> {
+    let filled: Vec<&Data> = values.iter().filter(|c| !is_blank(c)).copied().collect();
+
+    if !filled.is_empty()
+        && filled
+            .iter()
+            .all(|c| matches!(c, Data::Int(_) | Data::Float(_)))
+    {
+        let numbers: Vec<Option<f64>> = values
+            .iter()
+            .map(|c| match c {
+                Data::Int(v) => Some(*v as f64),
+                Data::Float(v) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        let integral = numbers.iter().flatten().all(|v| v.fract() == 0.0);
+        let s = Series::new(name, numbers);
+        return Ok(if integral {
+            s.cast(&DataType::Int64)?
+        } else {
+            s
+        });
+    }
+
+    if !filled.is_empty() && filled.iter().all(|c| matches!(c, Data::Bool(_))) {
+        let flags: Vec<Option<bool>> = values
+            .iter()
+            .map(|c| match c {
+                Data::Bool(v) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        return Ok(Series::new(name, flags));
+    }
+
+    if !filled.is_empty() && filled.iter().all(|c| as_datetime(c).is_some()) {
+        let times: Vec<Option<NaiveDateTime>> = values.iter().map(|c| as_datetime(c)).collect();
+        let date_only = times.iter().flatten().all(|v| v.time() == NaiveTime::MIN);
+        if date_only {
+            let dates: Vec<Option<NaiveDate>> = times.iter().map(|v| v.map(|t| t.date())).collect();
+            return Ok(Series::new(name, dates));
+        }
+        return Ok(
+            Series::new(name, times).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?
+        );
+    }
+
+    let texts: Vec<Option<String>> = values
+        .iter()
+        .map(|c| {
+            if is_blank(c) {
+                None
+            } else {
+                Some(c.to_string())
+            }
+        })
+        .collect();
+    Ok(Series::new(name, texts))
+}
@@ -0,0 +1,785 @@
+use polars::export::chrono::{Datelike, Days, Local, Months, NaiveDate};
+use polars::lazy::dsl::{col, lit, Expr};
+use polars::prelude::*;
+
+use regex::Regex;
+
+use std::format;
+
+use crate::convert;
+use crate::error::DataboardError;
+use crate::state::{
+    FilterGroup, FilterItem, FilterLogic, FilterMode, PeriodShift, Query, RelativeDate,
+    RelativeRange,
+};
+
+const ALL_MODES: [FilterMode; 17] = [
+    FilterMode::Single,
+    FilterMode::Multi,
+    FilterMode::MatchPrefix,
+    FilterMode::DateRange,
+    FilterMode::DigitalRange,
+    FilterMode::Contains,
+    FilterMode::NotContains,
+    FilterMode::Regex,
+    FilterMode::EndsWith,
+    FilterMode::NotEqual,
+    FilterMode::NotIn,
+    FilterMode::IsNull,
+    FilterMode::IsNotNull,
+    FilterMode::GreaterThan,
+    FilterMode::GreaterEqual,
+    FilterMode::LessThan,
+    FilterMode::LessEqual,
+];
+
+// 按过滤方式归类的列类型
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum ColumnKind {
+    Integer,
+    // 浮点数与定点小数
+    Float,
+    Boolean,
+    // 文本与分类
+    Text,
+    // 日期与日期时间, 日期时间按所在日期过滤
+    Date,
+    Duration,
+}
+
+impl ColumnKind {
+    fn of(dtype: &DataType) -> Option<ColumnKind> {
+        match dtype {
+            v if v.is_integer() => Some(ColumnKind::Integer),
+            v if v.is_float() => Some(ColumnKind::Float),
+            DataType::Decimal(_, _) => Some(ColumnKind::Float),
+            DataType::Boolean => Some(ColumnKind::Boolean),
+            DataType::String | DataType::Categorical(_, _) => Some(ColumnKind::Text),
+            DataType::Date | DataType::Datetime(_, _) => Some(ColumnKind::Date),
+            DataType::Duration(_) => Some(ColumnKind::Duration),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            ColumnKind::Integer => "整数",
+            ColumnKind::Float => "小数",
+            ColumnKind::Boolean => "布尔",
+            ColumnKind::Text => "文本",
+            ColumnKind::Date => "日期",
+            ColumnKind::Duration => "时长",
+        }
+    }
+
+    // 过滤方式与列类型的对应关系: 等值/集合/空值适用于全部类型, 文本匹配仅文本,
+    // 大小比较适用于数值/日期/时长, 数值范围用 DigitalRange, 日期范围用 DateRange
+    fn supports(&self, mode: FilterMode) -> bool {
+        match mode {
+            FilterMode::Single
+            | FilterMode::Multi
+            | FilterMode::NotEqual
+            | FilterMode::NotIn
+            | FilterMode::IsNull
+            | FilterMode::IsNotNull => true,
+            FilterMode::MatchPrefix
+            | FilterMode::Contains
+            | FilterMode::NotContains
+            | FilterMode::EndsWith
+            | FilterMode::Regex => *self == ColumnKind::Text,
+            FilterMode::GreaterThan
+            | FilterMode::GreaterEqual
+            | FilterMode::LessThan
+            | FilterMode::LessEqual => !matches!(self, ColumnKind::Text | ColumnKind::Boolean),
+            FilterMode::DigitalRange => matches!(
+                self,
+                ColumnKind::Integer | ColumnKind::Float | ColumnKind::Duration
+            ),
+            FilterMode::DateRange => *self == ColumnKind::Date,
+        }
+    }
+}
+
+// 解析过滤值, 空字符串为未填写(用于开区间)
+fn parse_values<T>(
+    item: &FilterItem,
+    expect: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<Option<T>>, DataboardError> {
+    item.value
+        .iter()
+        .map(|v| match v.trim() {
+            "" => Ok(None),
+            v => parse(v).map(Some).ok_or_else(|| {
+                DataboardError::InvalidQuery(format!("列 {} 的值 {} 应为{}", item.index, v, expect))
+            }),
+        })
+        .collect()
+}
+
+fn days_before(date: NaiveDate, days: u64) -> NaiveDate {
+    date.checked_sub_days(Days::new(days)).unwrap_or(date)
+}
+
+fn months_before(date: NaiveDate, months: u32) -> NaiveDate {
+    date.checked_sub_months(Months::new(months)).unwrap_or(date)
+}
+
+// 相对日期解析为起止日期, 上一期的截止日期不超过当期的开始日期, 月末日期超出时取月末
+fn relative_range(relative: &RelativeDate, as_of: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let (start, end) = match relative.range {
+        RelativeRange::Today => (as_of, as_of),
+        RelativeRange::Yesterday => (days_before(as_of, 1), days_before(as_of, 1)),
+        RelativeRange::Last7Days => (days_before(as_of, 6), as_of),
+        RelativeRange::Last30Days => (days_before(as_of, 29), as_of),
+        RelativeRange::WeekToDate => (
+            days_before(as_of, as_of.weekday().num_days_from_monday() as u64),
+            as_of,
+        ),
+        RelativeRange::MonthToDate => (as_of.with_day(1).unwrap_or(as_of), as_of),
+        RelativeRange::QuarterToDate => (
+            NaiveDate::from_ymd_opt(as_of.year(), as_of.month0() / 3 * 3 + 1, 1).unwrap_or(as_of),
+            as_of,
+        ),
+        RelativeRange::YearToDate => (as_of.with_ordinal(1).unwrap_or(as_of), as_of),
+    };
+    match relative.shift {
+        PeriodShift::Current => (start, end),
+        PeriodShift::LastYear => (months_before(start, 12), months_before(end, 12)),
+        PeriodShift::Previous => {
+            let previous = match relative.range {
+                RelativeRange::WeekToDate => days_before(start, 7),
+                RelativeRange::MonthToDate => months_before(start, 1),
+                RelativeRange::QuarterToDate => months_before(start, 3),
+                RelativeRange::YearToDate => months_before(start, 12),
+                // 固定天数的范围向前平移相同天数
+                _ => days_before(start, (end - start).num_days() as u64 + 1),
+            };
+            let length = (end - start).num_days() as u64;
+            let limit = days_before(start, 1);
+            (
+                previous,
+                previous
+                    .checked_add_days(Days::new(length))
+                    .map_or(limit, |v| v.min(limit)),
+            )
+        }
+    }
+}
+
+// 相对日期的基准日期
+fn as_of(query: &Query) -> Result<NaiveDate, DataboardError> {
+    match query.as_of.as_deref().map(str::trim) {
+        None | Some("") => Ok(Local::now().date_naive()),
+        Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| {
+            DataboardError::InvalidQuery(format!("基准日期 {} 应为 yyyy-mm-dd 格式", v))
+        }),
+    }
+}
+
+// 闭区间
+fn between(target: Expr, start: Expr, end: Expr) -> Expr {
+    target.is_between(start, end, ClosedInterval::Both)
+}
+
+// 取反, 空值视为不满足原条件
+fn negate(expr: Expr) -> Expr {
+    expr.not().fill_null(lit(true))
+}
+
+// 已解析的过滤值: 逐个的字面量与多选用的值集合
+struct Values {
+    items: Vec<Option<Expr>>,
+    set: Option<Expr>,
+}
+
+impl Values {
+    fn new<T: Clone>(
+        values: Vec<Option<T>>,
+        to_lit: impl Fn(T) -> Expr,
+        to_set: impl Fn(Vec<T>) -> Expr,
+    ) -> Values {
+        let set: Vec<T> = values.iter().flatten().cloned().collect();
+        Values {
+            items: values.into_iter().map(|v| v.map(&to_lit)).collect(),
+            set: if set.is_empty() {
+                None
+            } else {
+                Some(to_set(set))
+            },
+        }
+    }
+
+    fn first(&self) -> Option<Expr> {
+        self.items.first().cloned().flatten()
+    }
+
+    // 起止值, 缺少一端时为开区间
+    fn range(&self, target: Expr) -> Option<Expr> {
+        let start = self.first();
+        let end = self.items.get(1).cloned().flatten();
+        match (start, end) {
+            (Some(start), Some(end)) => Some(between(target, start, end)),
+            (Some(start), None) => Some(target.gt_eq(start)),
+            (None, Some(end)) => Some(target.lt_eq(end)),
+            (None, None) => None,
+        }
+    }
+}
+
+// 等值/集合/大小比较, 各列类型通用, 未填写值时忽略该条件
+fn compare(mode: FilterMode, target: Expr, values: &Values) -> Option<Expr> {
+    match mode {
+        FilterMode::Single => values.first().map(|v| target.eq(v)),
+        FilterMode::NotEqual => values.first().map(|v| target.neq_missing(v)),
+        FilterMode::Multi => values.set.clone().map(|v| target.is_in(v)),
+        FilterMode::NotIn => values.set.clone().map(|v| negate(target.is_in(v))),
+        FilterMode::GreaterThan => values.first().map(|v| target.gt(v)),
+        FilterMode::GreaterEqual => values.first().map(|v| target.gt_eq(v)),
+        FilterMode::LessThan => values.first().map(|v| target.lt(v)),
+        FilterMode::LessEqual => values.first().map(|v| target.lt_eq(v)),
+        FilterMode::DateRange | FilterMode::DigitalRange => values.range(target),
+        _ => None,
+    }
+}
+
+// 文本匹配, 多个值时满足任一值即可
+fn text_match(
+    item: &FilterItem,
+    mode: FilterMode,
+    target: Expr,
+    values: &[String],
+) -> Result<Option<Expr>, DataboardError> {
+    let values: Vec<String> = values.iter().filter(|v| !v.is_empty()).cloned().collect();
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let mut any: Option<Expr> = None;
+    for v in values {
+        let expr = match mode {
+            FilterMode::MatchPrefix => target.clone().str().starts_with(lit(v)),
+            FilterMode::EndsWith => target.clone().str().ends_with(lit(v)),
+            FilterMode::Regex => {
+                let pattern = if item.ignore_case {
+                    format!("(?i){}", v)
+                } else {
+                    v
+                };
+                Regex::new(&pattern).map_err(|e| {
+                    DataboardError::InvalidQuery(format!("列 {} 正则无效: {}", item.index, e))
+                })?;
+                target.clone().str().contains(lit(pattern), true)
+            }
+            _ => target.clone().str().contains_literal(lit(v)),
+        };
+        any = Some(match any {
+            Some(a) => a.or(expr),
+            None => expr,
+        });
+    }
+    Ok(match mode {
+        FilterMode::NotContains => any.map(negate),
+        _ => any,
+    })
+}
+
+// 单个搜索条件转换为表达式, 列类型不支持该方式时报错, 未填写值时返回空
+fn item_expr(
+    dtype: &DataType,
+    item: &FilterItem,
+    mode: FilterMode,
+    as_of: NaiveDate,
+) -> Result<Option<Expr>, DataboardError> {
+    let name = item.index.as_str();
+    let kind = ColumnKind::of(dtype).ok_or_else(|| {
+        DataboardError::TypeMismatch(format!("列 {} 的类型 {} 不支持过滤", name, dtype))
+    })?;
+    if !kind.supports(mode) {
+        let supported: Vec<String> = ALL_MODES
+            .iter()
+            .filter(|v| kind.supports(**v))
+            .map(|v| format!("{:?}", v))
+            .collect();
+        return Err(DataboardError::TypeMismatch(format!(
+            "{}列 {} 不支持 {:?} 过滤, 可用: {}",
+            kind.label(),
+            name,
+            mode,
+            supported.join("/")
+        )));
+    }
+    if item.relative.is_some() && mode != FilterMode::DateRange {
+        return Err(DataboardError::InvalidQuery(format!(
+            "列 {} 的相对日期仅支持 DateRange 过滤",
+            name
+        )));
+    }
+    match mode {
+        FilterMode::IsNull => return Ok(Some(col(name).is_null())),
+        FilterMode::IsNotNull => return Ok(Some(col(name).is_not_null())),
+        _ => {}
+    }
+
+    let expr = match kind {
+        ColumnKind::Integer => {
+            let v = parse_values(item, "整数", |v| v.parse::<i64>().ok())?;
+            let values = Values::new(
+                v,
+                |v| lit(v).cast(dtype.clone()),
+                |v| lit(Series::new("cm", v)).cast(dtype.clone()),
+            );
+            compare(mode, col(name), &values)
+        }
+        // 定点小数按浮点数比较
+        ColumnKind::Float => {
+            let v = parse_values(item, "数字", |v| v.parse::<f64>().ok())?;
+            let (target, ltype) = match dtype {
+                DataType::Decimal(_, _) => (col(name).cast(DataType::Float64), DataType::Float64),
+                _ => (col(name), dtype.clone()),
+            };
+            let values = Values::new(
+                v,
+                |v| lit(v).cast(ltype.clone()),
+                |v| lit(Series::new("cm", v)).cast(ltype.clone()),
+            );
+            compare(mode, target, &values)
+        }
+        ColumnKind::Boolean => {
+            let v = parse_values(item, "是/否", convert::parse_boolean)?;
+            let values = Values::new(v, lit, |v| lit(Series::new("cm", v)));
+            compare(mode, col(name), &values)
+        }
+        // 分类文本按字符串比较, 忽略大小写时统一转为小写
+        ColumnKind::Text => {
+            let mut v: Vec<String> = item.value.iter().map(|s| s.trim().to_string()).collect();
+            let mut target = match dtype {
+                DataType::String => col(name),
+                _ => col(name).cast(DataType::String),
+            };
+            if item.ignore_case && mode != FilterMode::Regex {
+                target = target.str().to_lowercase();
+                v = v.into_iter().map(|v| v.to_lowercase()).collect();
+            }
+            match mode {
+                FilterMode::MatchPrefix
+                | FilterMode::Contains
+                | FilterMode::NotContains
+                | FilterMode::EndsWith
+                | FilterMode::Regex => text_match(item, mode, target, &v)?,
+                _ => {
+                    let v = v
+                        .into_iter()
+                        .map(|v| Some(v).filter(|v| !v.is_empty()))
+                        .collect();
+                    let values = Values::new(v, lit, |v| lit(Series::new("cm", v)));
+                    compare(mode, target, &values)
+                }
+            }
+        }
+        ColumnKind::Date => {
+            let v = match &item.relative {
+                Some(relative) => {
+                    let (start, end) = relative_range(relative, as_of);
+                    vec![Some(start), Some(end)]
+                }
+                None => parse_values(item, " yyyy-mm-dd 格式的日期", |v| {
+                    NaiveDate::parse_from_str(v, "%Y-%m-%d").ok()
+                })?,
+            };
+            let target = match dtype {
+                DataType::Date => col(name),
+                _ => col(name).dt().date(),
+            };
+            let values = Values::new(
+                v,
+                |v| lit(v).cast(DataType::Date),
+                |v| lit(Series::new("cm", v)),
+            );
+            compare(mode, target, &values)
+        }
+        // 时长按毫秒数比较, 值的写法与数据配置一致
+        ColumnKind::Duration => {
+            let v = parse_values(item, "时长", convert::parse_duration)?;
+            let values = Values::new(v, lit, |v| lit(Series::new("cm", v)));
+            let target = col(name)
+                .cast(DataType::Duration(TimeUnit::Milliseconds))
+                .cast(DataType::Int64);
+            compare(mode, target, &values)
+        }
+    };
+    Ok(expr)
+}
+
+fn column_expr(
+    schema: &Schema,
+    item: &FilterItem,
+    mode: FilterMode,
+    as_of: NaiveDate,
+) -> Result<Option<Expr>, DataboardError> {
+    let dtype = schema
+        .get(&item.index)
+        .ok_or_else(|| DataboardError::UnknownColumn(item.index.clone()))?;
+    item_expr(dtype, item, mode, as_of)
+}
+
+// 条件组编译为一个表达式, 没有有效条件的组忽略
+fn group_expr(
+    schema: &Schema,
+    group: &FilterGroup,
+    as_of: NaiveDate,
+) -> Result<Option<Expr>, DataboardError> {
+    let mut exprs = Vec::<Expr>::default();
+    for item in group.items.iter() {
+        exprs.extend(column_expr(schema, item, item.mode, as_of)?);
+    }
+    for child in group.groups.iter() {
+        exprs.extend(group_expr(schema, child, as_of)?);
+    }
+    let expr = match group.logic {
+        FilterLogic::Or => exprs.into_iter().reduce(|a, b| a.or(b)),
+        FilterLogic::And => exprs.into_iter().reduce(|a, b| a.and(b)),
+        // 与 NotEqual/NotIn 一致, 空值视为不满足组内条件
+        FilterLogic::Not => exprs.into_iter().reduce(|a, b| a.and(b)).map(negate),
+    };
+    Ok(expr)
+}
+
+// 查询条件编译为过滤表达式, 第一列为空的行始终排除
+// search 中各条件之间为且的关系, 已配置为过滤项的列按配置的方式过滤; 条件树与其为且的关系
+// 相对日期按 as_of 解析
+pub fn compile(schema: &Schema, query: &Query) -> Result<Option<Expr>, DataboardError> {
+    let as_of = as_of(query)?;
+    let mut exprs: Vec<Expr> = schema
+        .get_at_index(0)
+        .map(|(name, _)| col(name).is_not_null())
+        .into_iter()
+        .collect();
+    for item in query.search.iter() {
+        let mode = query
+            .filters
+            .iter()
+            .find(|v| v.index == item.index)
+            .map_or(item.mode, |v| v.mode);
+        exprs.extend(column_expr(schema, item, mode, as_of)?);
+    }
+    if let Some(group) = &query.condition {
+        exprs.extend(group_expr(schema, group, as_of)?);
+    }
+    Ok(exprs.into_iter().reduce(|a, b| a.and(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> DataFrame {
+        df!(
+            "id" => [1, 2, 3, 4],
+            "region" => [Some("East"), Some("West"), None, Some("East")],
+            "amount" => [Some(10), Some(20), Some(30), None],
+            "price" => [Some(1.5), Some(2.25), None, Some(3.0)],
+            "day" => [
+                NaiveDate::from_ymd_opt(2024, 1, 5),
+                NaiveDate::from_ymd_opt(2024, 2, 10),
+                None,
+                NaiveDate::from_ymd_opt(2024, 3, 15),
+            ],
+        )
+        .unwrap()
+    }
+
+    // 按查询条件过滤, 返回满足条件的 id
+    fn ids(query: &str) -> Result<Vec<i32>, DataboardError> {
+        let query: Query = serde_json::from_str(&format!(
+            r#"{{"dimensions":{{"rows":[],"columns":[]}},"metrics":[],"rules":[],"filters":[],{}}}"#,
+            query
+        ))
+        .unwrap();
+        let df = frame();
+        let mut lf = df.clone().lazy();
+        if let Some(expr) = compile(&df.schema(), &query)? {
+            lf = lf.filter(expr);
+        }
+        let df = lf.collect().unwrap();
+        Ok(df
+            .column("id")
+            .unwrap()
+            .i32()
+            .unwrap()
+            .into_no_null_iter()
+            .collect())
+    }
+
+    #[test]
+    fn regex_matches_any_pattern() {
+        let v = ids(r#""search":[{"index":"region","mode":7,"value":["^E","^W"]}]"#).unwrap();
+        assert_eq!(v, vec![1, 2, 4]);
+        let v = ids(r#""search":[{"index":"region","mode":7,"value":["^w"],"ignore_case":true}]"#)
+            .unwrap();
+        assert_eq!(v, vec![2]);
+        assert!(matches!(
+            ids(r#""search":[{"index":"region","mode":7,"value":["^E","("]}]"#),
+            Err(DataboardError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn not_group_keeps_null() {
+        let not = ids(
+            r#""search":[],"condition":{"logic":2,"items":[{"index":"region","mode":0,"value":["East"]}]}"#,
+        )
+        .unwrap();
+        let not_equal = ids(r#""search":[{"index":"region","mode":9,"value":["East"]}]"#).unwrap();
+        assert_eq!(not, vec![2, 3]);
+        assert_eq!(not, not_equal);
+    }
+
+    fn search(index: &str, mode: FilterMode, value: &[&str]) -> Result<Vec<i32>, DataboardError> {
+        ids(&format!(
+            r#""search":[{{"index":"{}","mode":{},"value":{}}}]"#,
+            index,
+            mode as i32,
+            serde_json::to_string(value).unwrap()
+        ))
+    }
+
+    #[test]
+    fn operator_matrix() {
+        let text = [
+            FilterMode::MatchPrefix,
+            FilterMode::Contains,
+            FilterMode::NotContains,
+            FilterMode::Regex,
+            FilterMode::EndsWith,
+        ];
+        let compare = [
+            FilterMode::GreaterThan,
+            FilterMode::GreaterEqual,
+            FilterMode::LessThan,
+            FilterMode::LessEqual,
+        ];
+        for mode in ALL_MODES {
+            let is_text = text.contains(&mode);
+            let is_compare = compare.contains(&mode);
+            let numeric = !is_text && mode != FilterMode::DateRange;
+            assert_eq!(ColumnKind::Integer.supports(mode), numeric, "{:?}", mode);
+            assert_eq!(ColumnKind::Float.supports(mode), numeric, "{:?}", mode);
+            assert_eq!(ColumnKind::Duration.supports(mode), numeric, "{:?}", mode);
+            assert_eq!(
+                ColumnKind::Date.supports(mode),
+                !is_text && mode != FilterMode::DigitalRange,
+                "{:?}",
+                mode
+            );
+            assert_eq!(
+                ColumnKind::Text.supports(mode),
+                !is_compare && mode != FilterMode::DateRange && mode != FilterMode::DigitalRange,
+                "{:?}",
+                mode
+            );
+            assert_eq!(
+                ColumnKind::Boolean.supports(mode),
+                !is_text
+                    && !is_compare
+                    && mode != FilterMode::DateRange
+                    && mode != FilterMode::DigitalRange,
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_mode_error() {
+        let err = search("amount", FilterMode::MatchPrefix, &["1"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            DataboardError::TypeMismatch(
+                "整数列 amount 不支持 MatchPrefix 过滤, 可用: Single/Multi/DigitalRange/NotEqual/NotIn/IsNull/IsNotNull/GreaterThan/GreaterEqual/LessThan/LessEqual".to_string()
+            )
+            .to_string()
+        );
+        assert!(matches!(
+            search("day", FilterMode::DigitalRange, &["1", "2"]),
+            Err(DataboardError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            search("region", FilterMode::GreaterThan, &["a"]),
+            Err(DataboardError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_value_error() {
+        assert!(matches!(
+            search("amount", FilterMode::Single, &["x"]),
+            Err(DataboardError::InvalidQuery(v)) if v == "列 amount 的值 x 应为整数"
+        ));
+        assert!(matches!(
+            search("day", FilterMode::Single, &["2024/01/05"]),
+            Err(DataboardError::InvalidQuery(_))
+        ));
+        // 未填写的值忽略该条件
+        assert_eq!(
+            search("amount", FilterMode::Single, &[""]).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn modes_by_column_type() {
+        assert_eq!(
+            search("price", FilterMode::Single, &["2.25"]).unwrap(),
+            vec![2]
+        );
+        assert_eq!(
+            search("price", FilterMode::Multi, &["1.5", "3"]).unwrap(),
+            vec![1, 4]
+        );
+        assert_eq!(
+            search("price", FilterMode::NotIn, &["1.5"]).unwrap(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            search("amount", FilterMode::DigitalRange, &["15", ""]).unwrap(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            search("amount", FilterMode::LessThan, &["20"]).unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            search("day", FilterMode::DateRange, &["2024-02-01", "2024-03-15"]).unwrap(),
+            vec![2, 4]
+        );
+        assert_eq!(
+            search("day", FilterMode::GreaterEqual, &["2024-02-10"]).unwrap(),
+            vec![2, 4]
+        );
+        assert_eq!(search("day", FilterMode::IsNull, &[]).unwrap(), vec![3]);
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn range(range: RelativeRange, shift: PeriodShift, as_of: NaiveDate) -> (NaiveDate, NaiveDate) {
+        relative_range(&RelativeDate { range, shift }, as_of)
+    }
+
+    #[test]
+    fn relative_range_month_end() {
+        // 上月同期截止日期超出上月月末时取月末
+        assert_eq!(
+            range(
+                RelativeRange::MonthToDate,
+                PeriodShift::Previous,
+                date(2024, 3, 31)
+            ),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::MonthToDate,
+                PeriodShift::Previous,
+                date(2023, 3, 31)
+            ),
+            (date(2023, 2, 1), date(2023, 2, 28))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::MonthToDate,
+                PeriodShift::Previous,
+                date(2024, 1, 15)
+            ),
+            (date(2023, 12, 1), date(2023, 12, 15))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::QuarterToDate,
+                PeriodShift::Previous,
+                date(2024, 5, 15)
+            ),
+            (date(2024, 1, 1), date(2024, 2, 14))
+        );
+    }
+
+    #[test]
+    fn relative_range_leap_day() {
+        // 去年同期没有 2 月 29 日时取 2 月 28 日
+        assert_eq!(
+            range(
+                RelativeRange::Today,
+                PeriodShift::LastYear,
+                date(2024, 2, 29)
+            ),
+            (date(2023, 2, 28), date(2023, 2, 28))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::YearToDate,
+                PeriodShift::LastYear,
+                date(2024, 2, 29)
+            ),
+            (date(2023, 1, 1), date(2023, 2, 28))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::Yesterday,
+                PeriodShift::Previous,
+                date(2024, 3, 1)
+            ),
+            (date(2024, 2, 28), date(2024, 2, 28))
+        );
+    }
+
+    #[test]
+    fn relative_range_fixed_days() {
+        assert_eq!(
+            range(
+                RelativeRange::Last7Days,
+                PeriodShift::Current,
+                date(2024, 3, 10)
+            ),
+            (date(2024, 3, 4), date(2024, 3, 10))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::Last7Days,
+                PeriodShift::Previous,
+                date(2024, 3, 10)
+            ),
+            (date(2024, 2, 26), date(2024, 3, 3))
+        );
+        // 2024-03-07 为周四
+        assert_eq!(
+            range(
+                RelativeRange::WeekToDate,
+                PeriodShift::Current,
+                date(2024, 3, 7)
+            ),
+            (date(2024, 3, 4), date(2024, 3, 7))
+        );
+        assert_eq!(
+            range(
+                RelativeRange::WeekToDate,
+                PeriodShift::Previous,
+                date(2024, 3, 7)
+            ),
+            (date(2024, 2, 26), date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn relative_filter_as_of() {
+        let relative = r#"{"index":"day","mode":3,"value":[],"relative":{"range":5,"shift":1}}"#;
+        assert_eq!(
+            ids(&format!(r#""as_of":"2024-03-31","search":[{}]"#, relative)).unwrap(),
+            vec![2]
+        );
+        assert!(matches!(
+            ids(&format!(r#""as_of":"2024/03/31","search":[{}]"#, relative)),
+            Err(DataboardError::InvalidQuery(v)) if v.contains("2024/03/31")
+        ));
+    }
+}
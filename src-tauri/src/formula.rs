@@ -0,0 +1,678 @@
+use polars::export::chrono::{Local, NaiveDate};
+use polars::lazy::dsl::{binary_expr, coalesce, col, concat_str, lit, when, Expr, StrptimeOptions};
+use polars::prelude::*;
+
+use std::{fmt, format};
+
+// 计算规则解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormulaError {
+    // 出错位置(字符序号)
+    pub position: usize,
+    pub message: String,
+}
+impl FormulaError {
+    fn new(position: usize, message: impl Into<String>) -> FormulaError {
+        FormulaError {
+            position,
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "第{}个字符: {}", self.position + 1, self.message)
+    }
+}
+impl std::error::Error for FormulaError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    // 标识符: 列名、函数名或关键字
+    Ident(String),
+    // [列名] 或 `列名`
+    Column(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const OPERATORS: [&str; 16] = [
+    "==", "!=", "<>", "<=", ">=", "&&", "||", "=", "<", ">", "+", "-", "*", "/", "%", "!",
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+// 词法分析
+fn tokenize(calc: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let chars: Vec<char> = calc.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '(' => {
+                tokens.push((Token::LParen, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::RParen, start));
+                i += 1;
+            }
+            ',' | '，' => {
+                tokens.push((Token::Comma, start));
+                i += 1;
+            }
+            '\'' | '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(FormulaError::new(start, "字符串缺少结束引号")),
+                        Some('\\') if i + 1 < chars.len() => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(q) if *q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(v) => {
+                            text.push(*v);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push((Token::Text(text), start));
+            }
+            '[' | '`' => {
+                let close = if c == '[' { ']' } else { '`' };
+                let mut name = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(FormulaError::new(start, "列名缺少结束符号")),
+                        Some(v) if *v == close => {
+                            i += 1;
+                            break;
+                        }
+                        Some(v) => {
+                            name.push(*v);
+                            i += 1;
+                        }
+                    }
+                }
+                if name.trim().is_empty() {
+                    return Err(FormulaError::new(start, "列名不能为空"));
+                }
+                tokens.push((Token::Column(name.trim().to_string()), start));
+            }
+            _ if c.is_ascii_digit() => {
+                let mut is_float = false;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    if chars[i] == '.' {
+                        if is_float {
+                            break;
+                        }
+                        is_float = true;
+                    }
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let token = if is_float {
+                    text.parse::<f64>().map(Token::Float).ok()
+                } else {
+                    text.parse::<i64>().map(Token::Integer).ok()
+                };
+                match token {
+                    Some(t) => tokens.push((t, start)),
+                    None => return Err(FormulaError::new(start, format!("无效数字 {}", text))),
+                }
+            }
+            _ if is_ident_char(c) => {
+                while i < chars.len() && is_ident_char(chars[i]) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push((Token::Ident(text), start));
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                    Some(op) => {
+                        tokens.push((Token::Op(op), start));
+                        i += op.chars().count();
+                    }
+                    None => return Err(FormulaError::new(start, format!("无法识别的字符 {}", c))),
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // 原始公式长度, 用于定位末尾错误
+    end: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, p)| *p)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        ret
+    }
+
+    fn is_op(&self, ops: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(v)) if v.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, token: Token, name: &str) -> Result<(), FormulaError> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(FormulaError::new(self.position(), format!("缺少 {}", name)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.parse_and()?;
+        while self.is_op(&["||"]).is_some() || self.is_keyword("or") {
+            self.pos += 1;
+            left = left.or(self.parse_and()?);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.parse_not()?;
+        while self.is_op(&["&&"]).is_some() || self.is_keyword("and") {
+            self.pos += 1;
+            left = left.and(self.parse_not()?);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, FormulaError> {
+        if self.is_op(&["!"]).is_some() || self.is_keyword("not") {
+            self.pos += 1;
+            return Ok(self.parse_not()?.not());
+        }
+        self.parse_compare()
+    }
+
+    fn parse_compare(&mut self) -> Result<Expr, FormulaError> {
+        let left = self.parse_additive()?;
+        match self.is_op(&["=", "==", "!=", "<>", "<", "<=", ">", ">="]) {
+            Some(op) => {
+                self.pos += 1;
+                let right = self.parse_additive()?;
+                Ok(match op {
+                    "=" | "==" => left.eq(right),
+                    "!=" | "<>" => left.neq(right),
+                    "<" => left.lt(right),
+                    "<=" => left.lt_eq(right),
+                    ">" => left.gt(right),
+                    _ => left.gt_eq(right),
+                })
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.parse_multiplicative()?;
+        while let Some(op) = self.is_op(&["+", "-"]) {
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            left = if op == "+" {
+                left + right
+            } else {
+                left - right
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.is_op(&["*", "/", "%"]) {
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = match op {
+                "*" => left * right,
+                // 整数相除也返回小数, 如 利润/收入
+                "/" => binary_expr(left, Operator::TrueDivide, right),
+                _ => left % right,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, FormulaError> {
+        if self.is_op(&["-"]).is_some() {
+            self.pos += 1;
+            return Ok(lit(0) - self.parse_unary()?);
+        }
+        if self.is_op(&["+"]).is_some() {
+            self.pos += 1;
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, FormulaError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Integer(v)) => Ok(lit(v)),
+            Some(Token::Float(v)) => Ok(lit(v)),
+            Some(Token::Text(v)) => Ok(lit(v)),
+            Some(Token::Column(name)) => Ok(col(&name)),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                self.expect(Token::RParen, ")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    if self.peek() != Some(&Token::RParen) {
+                        loop {
+                            args.push(self.parse_or()?);
+                            if self.peek() == Some(&Token::Comma) {
+                                self.pos += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                    self.expect(Token::RParen, ")")?;
+                    return call(&name, args, position);
+                }
+                match name.to_lowercase().as_str() {
+                    "true" => Ok(lit(true)),
+                    "false" => Ok(lit(false)),
+                    "null" => Ok(lit(NULL)),
+                    _ => Ok(col(&name)),
+                }
+            }
+            Some(_) => Err(FormulaError::new(position, "表达式不完整")),
+            None => Err(FormulaError::new(position, "公式意外结束")),
+        }
+    }
+}
+
+// 取字符串字面量参数
+fn text_arg(args: &[Expr], index: usize, position: usize) -> Result<String, FormulaError> {
+    match args.get(index) {
+        Some(Expr::Literal(LiteralValue::String(v))) => Ok(v.to_string()),
+        _ => Err(FormulaError::new(
+            position,
+            format!("第{}个参数必须是字符串", index + 1),
+        )),
+    }
+}
+
+// 取整数字面量参数
+fn integer_arg(args: &[Expr], index: usize, position: usize) -> Result<i64, FormulaError> {
+    match args.get(index) {
+        Some(Expr::Literal(LiteralValue::Int(v))) => Ok(*v as i64),
+        Some(Expr::Literal(LiteralValue::Int64(v))) => Ok(*v),
+        _ => Err(FormulaError::new(
+            position,
+            format!("第{}个参数必须是整数", index + 1),
+        )),
+    }
+}
+
+fn parse_date(text: &str, position: usize) -> Result<NaiveDate, FormulaError> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| FormulaError::new(position, format!("无效日期 {}, 格式应为 yyyy-mm-dd", text)))
+}
+
+// 函数调用
+fn call(name: &str, args: Vec<Expr>, position: usize) -> Result<Expr, FormulaError> {
+    let fname = name.to_lowercase();
+    let arity = |min: usize, max: usize| -> Result<(), FormulaError> {
+        if args.len() < min || args.len() > max {
+            let expect = if min == max {
+                format!("{}", min)
+            } else if max == usize::MAX {
+                format!("至少{}", min)
+            } else {
+                format!("{}~{}", min, max)
+            };
+            Err(FormulaError::new(
+                position,
+                format!("函数 {} 需要{}个参数, 实际{}个", name, expect, args.len()),
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    let expr = match fname.as_str() {
+        // 条件
+        "if" | "iif" => {
+            arity(3, 3)?;
+            let mut it = args.into_iter();
+            let (c, t, e) = (it.next().unwrap(), it.next().unwrap(), it.next().unwrap());
+            when(c).then(t).otherwise(e)
+        }
+        "ifnull" | "coalesce" => {
+            arity(2, usize::MAX)?;
+            coalesce(&args)
+        }
+        "isnull" => {
+            arity(1, 1)?;
+            args[0].clone().is_null()
+        }
+        // 数值
+        "abs" => {
+            arity(1, 1)?;
+            args[0].clone().abs()
+        }
+        "round" => {
+            arity(1, 2)?;
+            let decimals = if args.len() > 1 {
+                integer_arg(&args, 1, position)?
+            } else {
+                0
+            };
+            args[0].clone().round(decimals.max(0) as u32)
+        }
+        "int" => {
+            arity(1, 1)?;
+            args[0].clone().cast(DataType::Int64)
+        }
+        "float" => {
+            arity(1, 1)?;
+            args[0].clone().cast(DataType::Float64)
+        }
+        "str" => {
+            arity(1, 1)?;
+            args[0].clone().cast(DataType::String)
+        }
+        // 字符串
+        "upper" => {
+            arity(1, 1)?;
+            args[0].clone().str().to_uppercase()
+        }
+        "lower" => {
+            arity(1, 1)?;
+            args[0].clone().str().to_lowercase()
+        }
+        "trim" => {
+            arity(1, 1)?;
+            args[0].clone().str().strip_chars(lit(NULL))
+        }
+        "len" => {
+            arity(1, 1)?;
+            args[0].clone().str().len_chars()
+        }
+        "concat" => {
+            arity(1, usize::MAX)?;
+            let items: Vec<Expr> = args.into_iter().map(|e| e.cast(DataType::String)).collect();
+            concat_str(items, "", false)
+        }
+        "substr" => {
+            arity(2, 3)?;
+            // 起始位置从1开始
+            let start = integer_arg(&args, 1, position)?;
+            let length = if args.len() > 2 {
+                lit(integer_arg(&args, 2, position)?.max(0) as u64)
+            } else {
+                lit(NULL)
+            };
+            args[0].clone().str().slice(lit((start - 1).max(0)), length)
+        }
+        "left" => {
+            arity(2, 2)?;
+            let length = integer_arg(&args, 1, position)?;
+            args[0]
+                .clone()
+                .str()
+                .slice(lit(0i64), lit(length.max(0) as u64))
+        }
+        "right" => {
+            arity(2, 2)?;
+            let length = integer_arg(&args, 1, position)?;
+            args[0].clone().str().slice(lit(-length.max(0)), lit(NULL))
+        }
+        "contains" => {
+            arity(2, 2)?;
+            args[0].clone().str().contains_literal(args[1].clone())
+        }
+        "startswith" => {
+            arity(2, 2)?;
+            args[0].clone().str().starts_with(args[1].clone())
+        }
+        "endswith" => {
+            arity(2, 2)?;
+            args[0].clone().str().ends_with(args[1].clone())
+        }
+        "replace" => {
+            arity(3, 3)?;
+            args[0]
+                .clone()
+                .str()
+                .replace_all(args[1].clone(), args[2].clone(), true)
+        }
+        // 日期
+        "date" => {
+            arity(1, 1)?;
+            lit(parse_date(&text_arg(&args, 0, position)?, position)?)
+        }
+        "today" => {
+            arity(0, 0)?;
+            lit(Local::now().date_naive())
+        }
+        "todate" => {
+            arity(1, 2)?;
+            let format = if args.len() > 1 {
+                text_arg(&args, 1, position)?
+            } else {
+                "%Y-%m-%d".to_string()
+            };
+            args[0].clone().str().to_date(StrptimeOptions {
+                format: Some(format),
+                strict: false,
+                ..Default::default()
+            })
+        }
+        "year" => {
+            arity(1, 1)?;
+            args[0].clone().dt().year()
+        }
+        "quarter" => {
+            arity(1, 1)?;
+            args[0].clone().dt().quarter()
+        }
+        "month" => {
+            arity(1, 1)?;
+            args[0].clone().dt().month()
+        }
+        "day" => {
+            arity(1, 1)?;
+            args[0].clone().dt().day()
+        }
+        "weekday" => {
+            arity(1, 1)?;
+            args[0].clone().dt().weekday()
+        }
+        "datediff" => {
+            arity(2, 2)?;
+            // 两个日期相差天数
+            args[0].clone().cast(DataType::Date).cast(DataType::Int32)
+                - args[1].clone().cast(DataType::Date).cast(DataType::Int32)
+        }
+        _ => {
+            return Err(FormulaError::new(position, format!("未知函数 {}", name)));
+        }
+    };
+    Ok(expr)
+}
+
+// 解析计算规则, 生成polars表达式
+pub fn parse(calc: &str) -> Result<Expr, FormulaError> {
+    let tokens = tokenize(calc)?;
+    if tokens.is_empty() {
+        return Err(FormulaError::new(0, "公式不能为空"));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: calc.chars().count(),
+    };
+    let expr = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(FormulaError::new(parser.position(), "存在多余的内容"));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 在两行数据上计算公式
+    fn eval(calc: &str) -> Series {
+        let df = df!(
+            "a" => [1i64, 2],
+            "my col" => ["x", "y"],
+        )
+        .unwrap();
+        let df = df
+            .lazy()
+            .select([parse(calc).unwrap().alias("v")])
+            .collect()
+            .unwrap();
+        df.column("v").unwrap().clone()
+    }
+
+    fn int(calc: &str) -> Vec<i64> {
+        let v = eval(calc).cast(&DataType::Int64).unwrap();
+        v.i64().unwrap().into_no_null_iter().collect()
+    }
+
+    fn float(calc: &str) -> f64 {
+        eval(calc)
+            .cast(&DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .get(0)
+            .unwrap()
+    }
+
+    fn text(calc: &str) -> String {
+        eval(calc).str().unwrap().get(0).unwrap().to_string()
+    }
+
+    fn boolean(calc: &str) -> Vec<bool> {
+        eval(calc).bool().unwrap().into_no_null_iter().collect()
+    }
+
+    fn error(calc: &str) -> FormulaError {
+        parse(calc).unwrap_err()
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        assert_eq!(int("1 + 2 * 3")[0], 7);
+        assert_eq!(int("(1 + 2) * 3")[0], 9);
+        assert_eq!(int("10 - 2 - 3")[0], 5);
+        assert_eq!(int("7 % 4 * 2")[0], 6);
+        assert_eq!(float("7 / 2"), 3.5);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(int("-2 * 3")[0], -6);
+        assert_eq!(int("- -2")[0], 2);
+        assert_eq!(int("2 * -[a]"), vec![-2, -4]);
+        assert_eq!(int("+[a] - -1"), vec![2, 3]);
+    }
+
+    #[test]
+    fn logic_precedence() {
+        assert_eq!(boolean("[a] > 1 and [a] < 3 or false"), vec![false, true]);
+        assert_eq!(boolean("[a] = 1 || [a] = 2 && false"), vec![true, false]);
+        assert_eq!(boolean("not [a] = 1"), vec![false, true]);
+        assert_eq!(boolean("![a] <> 1"), vec![true, false]);
+        assert_eq!(boolean("1 + 1 >= [a]"), vec![true, true]);
+    }
+
+    #[test]
+    fn columns_and_functions() {
+        assert_eq!(text("concat(`my col`，'-'， [a])"), "x-1");
+        assert_eq!(int("if([a] > 1, 10, 20)"), vec![20, 10]);
+        assert_eq!(float("round(2.346, 2)"), 2.35);
+        assert_eq!(text("substr('hello', 2, 3)"), "ell");
+        assert_eq!(text("left('hello', 2)"), "he");
+        assert_eq!(text("right('hello', 2)"), "lo");
+        assert_eq!(text("upper(trim(' ab '))"), "AB");
+        assert_eq!(text("'it\\'s'"), "it's");
+        assert_eq!(
+            int("datediff(date('2024-03-01'), date('2024-02-01'))")[0],
+            29
+        );
+        assert_eq!(int("year(todate('01/02/2023', '%d/%m/%Y'))")[0], 2023);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error(""), FormulaError::new(0, "公式不能为空"));
+        assert_eq!(error("1 +"), FormulaError::new(3, "公式意外结束"));
+        assert_eq!(error("[a] [a]"), FormulaError::new(4, "存在多余的内容"));
+        assert_eq!(error("(1 + 2"), FormulaError::new(6, "缺少 )"));
+        assert_eq!(error("1 + 'ab"), FormulaError::new(4, "字符串缺少结束引号"));
+        assert_eq!(error("1 # 2"), FormulaError::new(2, "无法识别的字符 #"));
+        assert_eq!(error("foo(1)"), FormulaError::new(0, "未知函数 foo"));
+        assert_eq!(
+            error("if(1, 2)"),
+            FormulaError::new(0, "函数 if 需要3个参数, 实际2个")
+        );
+        assert_eq!(
+            error("round([a], 'x')"),
+            FormulaError::new(0, "第2个参数必须是整数")
+        );
+        assert_eq!(
+            error("date('2024/01/01')").message,
+            "无效日期 2024/01/01, 格式应为 yyyy-mm-dd"
+        );
+        assert_eq!(error("1 +").to_string(), "第4个字符: 公式意外结束");
+    }
+}
@@ -0,0 +1,217 @@
+use polars::prelude::*;
+
+use serde::{Serialize, Serializer};
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::DataboardError;
+
+// 进度事件的最小间隔
+const REPORT_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum JobStage {
+    // 读取文件
+    Read = 0,
+    // 统计行数
+    Count = 1,
+    // 类型转换
+    Transform = 2,
+    // 查询计算
+    Search = 3,
+    // 保存结果
+    Save = 4,
+    // 已完成
+    Done = 5,
+}
+impl Serialize for JobStage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct JobProgress {
+    pub id: String,
+    pub stage: JobStage,
+    // 当前阶段已处理的行数
+    pub rows: u64,
+}
+
+type Reporter = Arc<dyn Fn(JobProgress) + Send + Sync>;
+
+// 后台任务, 记录进度并在计算过程中检查是否已取消
+#[derive(Clone)]
+pub struct Job {
+    id: String,
+    stage: Arc<Mutex<JobStage>>,
+    rows: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+    reported: Arc<Mutex<Instant>>,
+    reporter: Option<Reporter>,
+}
+// 不上报进度的任务
+impl Default for Job {
+    fn default() -> Self {
+        Job::new("", None)
+    }
+}
+
+impl Job {
+    pub fn new(id: &str, reporter: Option<Reporter>) -> Job {
+        Job {
+            id: id.to_string(),
+            stage: Arc::new(Mutex::new(JobStage::Read)),
+            rows: Arc::new(AtomicU64::new(0)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            reported: Arc::new(Mutex::new(Instant::now())),
+            reporter,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn cancelled_error(&self) -> DataboardError {
+        DataboardError::Cancelled(self.id.clone())
+    }
+
+    fn report(&self) {
+        if let Some(reporter) = &self.reporter {
+            reporter(JobProgress {
+                id: self.id.clone(),
+                stage: *self.stage.lock().unwrap(),
+                rows: self.rows.load(Ordering::Relaxed),
+            });
+        }
+    }
+
+    // 进入新阶段, 已取消时返回错误
+    pub fn stage(&self, stage: JobStage) -> Result<(), DataboardError> {
+        if self.is_cancelled() {
+            return Err(self.cancelled_error());
+        }
+        *self.stage.lock().unwrap() = stage;
+        self.rows.store(0, Ordering::Relaxed);
+        self.report();
+        Ok(())
+    }
+
+    // 在查询计划中加入计数节点, 流式执行时每批数据经过都会更新进度并检查是否已取消
+    pub fn track(&self, lf: LazyFrame) -> LazyFrame {
+        if self.reporter.is_none() {
+            return lf;
+        }
+        let job = self.clone();
+        lf.map(
+            move |df| {
+                if job.is_cancelled() {
+                    polars_bail!(ComputeError: "任务 {} 已取消", job.id);
+                }
+                job.rows.fetch_add(df.height() as u64, Ordering::Relaxed);
+                let mut reported = job.reported.lock().unwrap();
+                if reported.elapsed() >= REPORT_INTERVAL {
+                    *reported = Instant::now();
+                    drop(reported);
+                    job.report();
+                }
+                Ok(df)
+            },
+            AllowedOptimizations {
+                streaming: true,
+                ..Default::default()
+            },
+            None,
+            Some("progress"),
+        )
+    }
+
+    // 执行查询计划并上报进度
+    pub fn collect(&self, lf: LazyFrame) -> Result<DataFrame, DataboardError> {
+        self.execute(lf.with_streaming(true))
+    }
+
+    // 使用默认引擎执行, 用于流式引擎不支持的计划
+    pub fn collect_default(&self, lf: LazyFrame) -> Result<DataFrame, DataboardError> {
+        self.execute(lf.with_streaming(false))
+    }
+
+    // 统计行数, 进度按读取的行数上报
+    pub fn count(&self, lf: LazyFrame) -> Result<usize, DataboardError> {
+        let df = self.run(self.track(lf).select([len()]).with_streaming(true))?;
+        let rows = df.get_columns()[0]
+            .cast(&DataType::UInt64)?
+            .u64()?
+            .get(0)
+            .unwrap_or(0);
+        Ok(rows as usize)
+    }
+
+    fn execute(&self, lf: LazyFrame) -> Result<DataFrame, DataboardError> {
+        self.run(self.track(lf))
+    }
+
+    fn run(&self, lf: LazyFrame) -> Result<DataFrame, DataboardError> {
+        match lf.collect() {
+            Ok(df) => {
+                self.report();
+                Ok(df)
+            }
+            Err(_) if self.is_cancelled() => Err(self.cancelled_error()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn finish(&self) {
+        *self.stage.lock().unwrap() = JobStage::Done;
+        self.report();
+    }
+}
+
+#[derive(Default)]
+pub struct JobStore {
+    // 运行中的任务
+    jobs: HashMap<String, Job>,
+}
+impl JobStore {
+    // 登记任务, 同名任务会被取消
+    pub fn start(&mut self, job: Job) {
+        if let Some(v) = self.jobs.insert(job.id.clone(), job) {
+            v.cancel();
+        }
+    }
+
+    pub fn cancel(&mut self, id: &str) -> bool {
+        match self.jobs.get(id) {
+            Some(v) => {
+                v.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 移除已结束的任务, 同名的新任务不受影响
+    pub fn finish(&mut self, job: &Job) {
+        if let Some(v) = self.jobs.get(&job.id) {
+            if Arc::ptr_eq(&v.cancelled, &job.cancelled) {
+                self.jobs.remove(&job.id);
+            }
+        }
+    }
+}
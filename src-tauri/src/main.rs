@@ -1,6 +1,16 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod board;
+mod clean;
+mod column;
+mod convert;
+mod csv;
+mod error;
+mod excel;
+mod filter;
+mod formula;
+mod job;
 mod state;
 
 use serde_json::{
@@ -12,112 +22,395 @@ use serde_json::{
 
 use std::{
     format,
-    sync::Mutex,
+    path::Path,
+    sync::{Arc, Mutex},
 };
+use tauri::{AppHandle, Emitter, Manager};
+use crate::board::{self, Board, RecentBoard};
+use crate::convert::{self, TypeSuggestion};
+use crate::csv::CsvOptions;
+use crate::error::DataboardError;
+use crate::excel::{self, ExcelOptions};
+use crate::job::{Job, JobProgress, JobStage, JobStore};
 use crate::state::{
+    Dataset,
+    DatasetInfo,
+    EtlReport,
+    FileFormat,
+    JoinSetting,
     Setting,
     Query,
+    SearchResult,
     StateStore,
 };
 
 struct StateStoreWrapper(pub Mutex<StateStore>);
+struct JobStoreWrapper(pub Mutex<JobStore>);
+
+// 后台任务进度事件
+const JOB_EVENT: &str = "databoard://job";
+
+// 在后台线程执行耗时任务, 指定任务编号时上报进度并可取消
+async fn run_job<T, F>(app: AppHandle, job: Option<String>, task: F) -> Result<T, DataboardError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppHandle, &Job) -> Result<T, DataboardError> + Send + 'static,
+{
+    let id = job.unwrap_or_default();
+    let job = if id.is_empty() {
+        Job::default()
+    } else {
+        let emitter = app.clone();
+        let reporter = Arc::new(move |progress: JobProgress| {
+            if let Err(e) = emitter.emit(JOB_EVENT, progress) {
+                log::warn!("emit job progress failed: {}", e);
+            }
+        });
+        let job = Job::new(&id, Some(reporter));
+        app.state::<JobStoreWrapper>().0.lock().unwrap().start(job.clone());
+        job
+    };
+
+    let handle = app.clone();
+    let running = job.clone();
+    let ret = tauri::async_runtime::spawn_blocking(move || task(&handle, &running))
+        .await
+        .map_err(|e| DataboardError::InvalidQuery(e.to_string()))
+        .and_then(|v| v);
+    app.state::<JobStoreWrapper>().0.lock().unwrap().finish(&job);
+    if ret.is_ok() {
+        job.finish();
+    }
+    ret
+}
 
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}!", name)
 }
 
+// 数据集名称及是否为指定的名称, 未指定时使用文件名, 目录或通配符使用所在目录名
+fn dataset_id(dataset: Option<String>, path: &str) -> (String, bool) {
+    match dataset {
+        Some(v) if !v.trim().is_empty() => (v.trim().to_string(), true),
+        _ => {
+            let p = Path::new(path);
+            let p = if path.contains(['*', '?', '[']) {
+                p.parent().unwrap_or(p)
+            } else {
+                p
+            };
+            let name = if p.is_dir() { p.file_name() } else { p.file_stem() };
+            let name = name.map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string());
+            (name, false)
+        }
+    }
+}
+
 #[tauri::command]
 async fn databoard_loader(
-    path: &str,
+    app: AppHandle,
+    path: String,
+    dataset: Option<String>,
+    format: Option<FileFormat>,
+    csv: Option<CsvOptions>,
+    excel: Option<ExcelOptions>,
+    job: Option<String>,
+) -> Result<String, DataboardError> {
+    // 由文件名生成的名称与已有数据集重名时加后缀, 不替换已有数据集
+    let (id, replace) = dataset_id(dataset, &path);
+    run_job(app, job, move |app, job| {
+        job.stage(JobStage::Read)?;
+        let mut data = Dataset::default();
+        data.read_file(&path, format, csv, excel)?;
+        job.stage(JobStage::Count)?;
+        data.count(job)?;
+        let id = app.state::<StateStoreWrapper>().0.lock().unwrap().insert(&id, data, replace);
+        Ok(id)
+    })
+    .await
+}
+
+// 参数与前端调用的字段一一对应
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn databoard_loader_files(
+    app: AppHandle,
+    paths: Vec<String>,
+    dataset: Option<String>,
+    format: Option<FileFormat>,
+    csv: Option<CsvOptions>,
+    excel: Option<ExcelOptions>,
+    source_column: Option<bool>,
+    job: Option<String>,
+) -> Result<String, DataboardError> {
+    let first = paths
+        .first()
+        .ok_or_else(|| DataboardError::InvalidQuery("文件列表不能为空".to_string()))?;
+    let (id, replace) = dataset_id(dataset, first);
+    run_job(app, job, move |app, job| {
+        let mut data = Dataset::default();
+        data.read_files(&paths, format, csv, excel, source_column.unwrap_or(false), job)?;
+        job.stage(JobStage::Count)?;
+        data.count(job)?;
+        let id = app.state::<StateStoreWrapper>().0.lock().unwrap().insert(&id, data, replace);
+        Ok(id)
+    })
+    .await
+}
+
+#[tauri::command]
+fn databoard_sheets(path: &str) -> Result<Vec<String>, DataboardError> {
+    excel::sheet_names(path)
+}
+
+#[tauri::command]
+fn databoard_datasets(
+    state: tauri::State<'_, StateStoreWrapper>,
+) -> Result<Vec<DatasetInfo>, DataboardError> {
+    Ok(state.0.lock().unwrap().list())
+}
+#[tauri::command]
+fn databoard_remove(
+    dataset: &str,
     state: tauri::State<'_, StateStoreWrapper>,
-) -> Result<bool, ()> {
-    Ok(state.0.lock().unwrap().read_csv(path))
+) -> Result<bool, DataboardError> {
+    Ok(state.0.lock().unwrap().remove(dataset))
+}
+
+#[tauri::command]
+async fn databoard_join(
+    app: AppHandle,
+    setting: JoinSetting,
+    job: Option<String>,
+) -> Result<DatasetInfo, DataboardError> {
+    run_job(app, job, move |app, job| {
+        let (left, right) = {
+            let state = app.state::<StateStoreWrapper>();
+            let mut store = state.0.lock().unwrap();
+            (store.records(&setting.left)?, store.records(&setting.right)?)
+        };
+        job.stage(JobStage::Read)?;
+        let mut data = Dataset::join(left, right, &setting, job)?;
+        job.stage(JobStage::Count)?;
+        data.count(job)?;
+        let id = setting.target.trim();
+        let info = data.info(id);
+        app.state::<StateStoreWrapper>().0.lock().unwrap().insert(id, data, true);
+        Ok(info)
+    })
+    .await
+}
+
+// 最近打开项目的记录目录
+fn recent_dir(app: &AppHandle) -> Result<std::path::PathBuf, DataboardError> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| DataboardError::Io(e.to_string()))
 }
 
 #[tauri::command]
-fn databoard_count(state: tauri::State<'_, StateStoreWrapper>) -> usize {
-    state.0.lock().unwrap().count()
+fn databoard_board_save(
+    app: AppHandle,
+    path: &str,
+    mut board: Board,
+) -> Result<bool, DataboardError> {
+    board::save(path, &mut board)?;
+    board::remember(&recent_dir(&app)?, path, &board.name)?;
+    Ok(true)
 }
+
 #[tauri::command]
-fn databoard_columns(state: tauri::State<'_, StateStoreWrapper>) -> JsonValue {
-    let ret = json_from_struct(state.0.lock().unwrap().columns()).unwrap();
+fn databoard_board_open(app: AppHandle, path: &str) -> Result<Board, DataboardError> {
+    let board = board::open(path)?;
+    board::remember(&recent_dir(&app)?, path, &board.name)?;
+    Ok(board)
+}
+
+#[tauri::command]
+fn databoard_board_recent(app: AppHandle) -> Result<Vec<RecentBoard>, DataboardError> {
+    Ok(board::recent(&recent_dir(&app)?))
+}
+
+#[tauri::command]
+fn databoard_cancel(
+    job: &str,
+    jobs: tauri::State<'_, JobStoreWrapper>,
+) -> Result<bool, DataboardError> {
+    Ok(jobs.0.lock().unwrap().cancel(job))
+}
+
+#[tauri::command]
+async fn databoard_count(
+    app: AppHandle,
+    dataset: String,
+    job: Option<String>,
+) -> Result<usize, DataboardError> {
+    run_job(app, job, move |app, job| {
+        // 只在取出计划和保存行数时持有锁, 统计期间不影响其他命令
+        let (records, rows, version) = {
+            let state = app.state::<StateStoreWrapper>();
+            let mut store = state.0.lock().unwrap();
+            let data = store.dataset(&dataset)?;
+            let (records, rows) = data.source();
+            (records, rows, data.version())
+        };
+        if let Some(rows) = rows {
+            return Ok(rows);
+        }
+        job.stage(JobStage::Count)?;
+        let rows = job.count(records)?;
+        app.state::<StateStoreWrapper>().0.lock().unwrap().dataset_at(&dataset, version)?.set_rows(rows);
+        Ok(rows)
+    })
+    .await
+}
+#[tauri::command]
+fn databoard_columns(
+    dataset: &str,
+    state: tauri::State<'_, StateStoreWrapper>,
+) -> Result<JsonValue, DataboardError> {
+    let ret = json_from_struct(state.0.lock().unwrap().dataset(dataset)?.columns()?)?;
     let result = json!({
         "columns": ret
     });
-    result
+    Ok(result)
+}
+#[tauri::command]
+async fn databoard_suggest_types(
+    app: AppHandle,
+    dataset: String,
+    job: Option<String>,
+) -> Result<Vec<TypeSuggestion>, DataboardError> {
+    run_job(app, job, move |app, job| {
+        // 只在取出原始数据时持有锁, 抽样推断期间不影响其他命令
+        let (records, _) = app.state::<StateStoreWrapper>().0.lock().unwrap().dataset(&dataset)?.source();
+        job.stage(JobStage::Read)?;
+        convert::suggest_types(records, job)
+    })
+    .await
 }
+
 #[tauri::command]
-fn databoard_unique(name:String, state: tauri::State<'_, StateStoreWrapper>) -> JsonValue {
-    let ret = state.0.lock().unwrap().column_unique(name);
-    json_from_struct(&ret).unwrap()
+async fn databoard_unique(
+    dataset: &str,
+    name: String,
+    state: tauri::State<'_, StateStoreWrapper>,
+) -> Result<JsonValue, DataboardError> {
+    let standard = state.0.lock().unwrap().dataset(dataset)?.standard()?;
+    let ret = Dataset::column_unique(standard, name)?;
+    Ok(json_from_struct(&ret)?)
 }
 #[tauri::command]
-fn databoard_preview(count: usize, state: tauri::State<'_, StateStoreWrapper>) -> JsonValue {
-    let mut data = state.0.lock().unwrap();
-    let mut result = data.preview(count);
+fn databoard_preview(
+    dataset: &str,
+    count: usize,
+    state: tauri::State<'_, StateStoreWrapper>,
+) -> Result<JsonValue, DataboardError> {
+    let mut store = state.0.lock().unwrap();
+    let data = store.dataset(dataset)?;
+    let mut result = data.preview(count)?;
 
-    let ret = data.to_string(&mut result);
-    json_from_str(ret.as_str()).unwrap()
+    let ret = Dataset::to_string(&mut result)?;
+    Ok(json_from_str(ret.as_str())?)
 }
 
 #[tauri::command]
 async fn databoard_setting(
+    app: AppHandle,
+    dataset: String,
     setting: Setting,
-    state: tauri::State<'_, StateStoreWrapper>,
-) -> Result<bool, String> {
-    let mut data = state.0.lock().unwrap();
-    let ret = data.etl(setting.columns);
-    match ret {
-        Ok(_) => Ok(true),
-        Err(e) => Err(format!("配置错误 {}", e.to_string())),
-    }
+    job: Option<String>,
+) -> Result<EtlReport, DataboardError> {
+    run_job(app, job, move |app, job| {
+        // 只在取出原始数据和保存结果时持有锁, 转换和统计期间不影响其他命令
+        let (records, rows, version) = {
+            let state = app.state::<StateStoreWrapper>();
+            let mut store = state.0.lock().unwrap();
+            let data = store.dataset(&dataset)?;
+            let (records, rows) = data.source();
+            (records, rows, data.version())
+        };
+        job.stage(JobStage::Transform)?;
+        let (standard, report) = Dataset::etl(records, rows, setting, job)?;
+        // 转换期间数据集被替换时不保存结果
+        app.state::<StateStoreWrapper>().0.lock().unwrap().dataset_at(&dataset, version)?.set_standard(standard, report.rows);
+        Ok(report)
+    })
+    .await
 }
 
 #[tauri::command]
-fn databoard_search(
+async fn databoard_search(
+    app: AppHandle,
+    dataset: String,
     playload: Query,
-    state: tauri::State<'_, StateStoreWrapper>,
-) -> Result<JsonValue, String> {
-    let mut data = state.0.lock().unwrap();
-    let mut result = data.search(playload);
-    let ret = data.to_string(&mut result);
+    job: Option<String>,
+) -> Result<JsonValue, DataboardError> {
+    run_job(app, job, move |app, job| {
+        // 只在取出计划和保存结果时持有锁, 计算期间不影响其他命令
+        let (standard, version) = {
+            let state = app.state::<StateStoreWrapper>();
+            let mut store = state.0.lock().unwrap();
+            let data = store.dataset(&dataset)?;
+            (data.standard()?, data.version())
+        };
+        job.stage(JobStage::Search)?;
+        let result = SearchResult::new(Dataset::search(standard, playload, job)?);
+        let mut records = result.page(0, 30, job)?;
+        app.state::<StateStoreWrapper>().0.lock().unwrap().dataset_at(&dataset, version)?.set_result(result);
+        let ret = Dataset::to_string(&mut records)?;
 
-    let columns = result.get_column_names();
-    let records = json_from_str::<JsonValue>(ret.as_str()).unwrap();
+        let columns = records.get_column_names();
+        let records = json_from_str::<JsonValue>(ret.as_str())?;
 
-    Ok(json! ({
-        "columns": columns,
-        "records": records,
-    }))
+        Ok(json! ({
+            "columns": columns,
+            "records": records,
+        }))
+    })
+    .await
 }
 
 #[tauri::command]
-fn databoard_search_more(
+async fn databoard_search_more(
+    dataset: &str,
     start: i32,
     state: tauri::State<'_, StateStoreWrapper>,
-) -> Result<JsonValue, String> {
-    let mut data = state.0.lock().unwrap();
-    let mut result = data.records(start.into(), 100);
-    let ret = data.to_string(&mut result);
+) -> Result<JsonValue, DataboardError> {
+    // 分页缓存有单独的锁, 读取期间不影响其他命令
+    let result = state.0.lock().unwrap().dataset(dataset)?.pages();
+    let mut records = result.page(start.max(0) as usize, 100, &Job::default())?;
+    let ret = Dataset::to_string(&mut records)?;
 
-    Ok(json_from_str(ret.as_str()).unwrap())
+    Ok(json_from_str(ret.as_str())?)
 }
 #[tauri::command]
-fn databoard_search_save(
-    path: &str,
-    state: tauri::State<'_, StateStoreWrapper>,
-) -> Result<bool, String> {
-    let mut data = state.0.lock().unwrap();
-    Ok(data.save_csv(path))
+async fn databoard_search_save(
+    app: AppHandle,
+    dataset: String,
+    path: String,
+    format: Option<FileFormat>,
+    job: Option<String>,
+) -> Result<bool, DataboardError> {
+    run_job(app, job, move |app, job| {
+        let result = app.state::<StateStoreWrapper>().0.lock().unwrap().dataset(&dataset)?.result();
+        job.stage(JobStage::Search)?;
+        Dataset::save_file(result, &path, format, job)?;
+        Ok(true)
+    })
+    .await
 }
 
 fn main() {
     env_logger::init();
     
     let state = StateStoreWrapper(Mutex::new(StateStore::default()));
+    let jobs = JobStoreWrapper(Mutex::new(JobStore::default()));
     tauri::Builder::default()
         .manage(state)
+        .manage(jobs)
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_dialog::init())
@@ -125,8 +418,18 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             greet,
             databoard_loader,
+            databoard_loader_files,
+            databoard_sheets,
+            databoard_datasets,
+            databoard_remove,
+            databoard_join,
+            databoard_cancel,
+            databoard_board_save,
+            databoard_board_open,
+            databoard_board_recent,
             databoard_count,
             databoard_columns,
+            databoard_suggest_types,
             databoard_unique,
             databoard_preview,
             databoard_setting,
@@ -1,29 +1,132 @@
-use regex::Regex;
-
-use polars::lazy::dsl::{col, Expr};
+use polars::export::chrono::NaiveDate;
+use polars::lazy::dsl::{binary_expr, col, concat_str, lit, when, Expr};
 use polars::prelude::*;
 
+use crate::clean::{self, CleanReport, CleanStep};
+use crate::column::ColumnOperations;
+use crate::convert::{self, ParseOptions};
+use crate::csv::{self, CsvOptions};
+use crate::error::DataboardError;
+use crate::excel::{self, ExcelOptions};
+use crate::filter;
+use crate::formula;
+use crate::job::{Job, JobStage};
+
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use std::{
     collections::HashMap,
     format,
     io::{BufWriter, Write as _},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ColumnType {
-    String = 0,
-    Integer = 1,
-    Float = 2,
-    Date = 3,
+    String,
+    // 32位整数
+    Integer,
+    // 32位小数
+    Float,
+    Date,
+    Boolean,
+    // 日期时间, 精度和时区, 无时区时按本地时间处理
+    Datetime(TimeUnit, Option<String>),
+    // 时长, 精度
+    Duration(TimeUnit),
+    Int64,
+    Float64,
+    // 定点小数, 总位数(为空时不限制)和小数位数
+    Decimal(Option<usize>, usize),
+    // 分类文本, 适合取值较少的列
+    Categorical,
+}
+impl ColumnType {
+    // 类型编码, 与前端一致
+    pub fn code(&self) -> i32 {
+        match self {
+            ColumnType::String => 0,
+            ColumnType::Integer => 1,
+            ColumnType::Float => 2,
+            ColumnType::Date => 3,
+            ColumnType::Boolean => 4,
+            ColumnType::Datetime(_, _) => 5,
+            ColumnType::Duration(_) => 6,
+            ColumnType::Int64 => 7,
+            ColumnType::Float64 => 8,
+            ColumnType::Decimal(_, _) => 9,
+            ColumnType::Categorical => 10,
+        }
+    }
+}
+
+fn time_unit_name(unit: TimeUnit) -> &'static str {
+    match unit {
+        TimeUnit::Nanoseconds => "ns",
+        TimeUnit::Microseconds => "us",
+        TimeUnit::Milliseconds => "ms",
+    }
+}
+
+fn time_unit(name: Option<&str>) -> Option<TimeUnit> {
+    match name {
+        None | Some("ms") => Some(TimeUnit::Milliseconds),
+        Some("us") => Some(TimeUnit::Microseconds),
+        Some("ns") => Some(TimeUnit::Nanoseconds),
+        _ => None,
+    }
 }
+
+// 带参数的类型以对象传递, 如 {"type": 5, "unit": "ms", "tz": "Asia/Shanghai"}
+#[derive(Serialize, Deserialize)]
+struct ColumnTypeOptions {
+    #[serde(rename = "type")]
+    code: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tz: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precision: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scale: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColumnTypeValue {
+    Code(i32),
+    Options(ColumnTypeOptions),
+}
+
 impl Serialize for ColumnType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_i32(*self as i32)
+        let mut options = ColumnTypeOptions {
+            code: self.code(),
+            unit: None,
+            tz: None,
+            precision: None,
+            scale: None,
+        };
+        match self {
+            ColumnType::Datetime(unit, tz) => {
+                options.unit = Some(time_unit_name(*unit).to_string());
+                options.tz = tz.clone();
+            }
+            ColumnType::Duration(unit) => {
+                options.unit = Some(time_unit_name(*unit).to_string());
+            }
+            ColumnType::Decimal(precision, scale) => {
+                options.precision = *precision;
+                options.scale = Some(*scale);
+            }
+            _ => return serializer.serialize_i32(self.code()),
+        }
+        options.serialize(serializer)
     }
 }
 
@@ -32,20 +135,331 @@ impl<'de> Deserialize<'de> for ColumnType {
     where
         D: Deserializer<'de>,
     {
-        let value = i32::deserialize(deserializer)?;
-        Ok(match value {
+        let options = match ColumnTypeValue::deserialize(deserializer)? {
+            ColumnTypeValue::Code(code) => ColumnTypeOptions {
+                code,
+                unit: None,
+                tz: None,
+                precision: None,
+                scale: None,
+            },
+            ColumnTypeValue::Options(v) => v,
+        };
+        let unit = time_unit(options.unit.as_deref()).ok_or_else(|| {
+            serde::de::Error::custom(format!("不支持的时间精度 {:?}", options.unit))
+        })?;
+        Ok(match options.code {
             1 => ColumnType::Integer,
             2 => ColumnType::Float,
             3 => ColumnType::Date,
+            4 => ColumnType::Boolean,
+            5 => ColumnType::Datetime(unit, options.tz.filter(|v| !v.is_empty())),
+            6 => ColumnType::Duration(unit),
+            7 => ColumnType::Int64,
+            8 => ColumnType::Float64,
+            9 => ColumnType::Decimal(options.precision, options.scale.unwrap_or(2)),
+            10 => ColumnType::Categorical,
             _ => ColumnType::String,
         })
     }
 }
 
+// 值无法转换为配置类型时的处理方式
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum FailurePolicy {
+    // 置为空值
+    #[default]
+    Null = 0,
+    // 使用类型默认值
+    Default = 1,
+    // 排除所在行
+    Reject = 2,
+    // 终止配置
+    Abort = 3,
+}
+impl Serialize for FailurePolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for FailurePolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => FailurePolicy::Default,
+            2 => FailurePolicy::Reject,
+            3 => FailurePolicy::Abort,
+            _ => FailurePolicy::Null,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum FileFormat {
+    Csv = 0,
+    Excel = 1,
+    Parquet = 2,
+    Json = 3,
+    Ipc = 4,
+}
+impl Serialize for FileFormat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileFormat {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => FileFormat::Excel,
+            2 => FileFormat::Parquet,
+            3 => FileFormat::Json,
+            4 => FileFormat::Ipc,
+            _ => FileFormat::Csv,
+        })
+    }
+}
+impl FileFormat {
+    // 按文件扩展名识别格式, 无法识别时按CSV处理
+    pub fn from_path(path: &str) -> FileFormat {
+        FileFormat::from_extension(Path::new(path)).unwrap_or(FileFormat::Csv)
+    }
+
+    // 按文件扩展名识别格式, 不支持的扩展名返回空
+    pub fn from_extension(path: &Path) -> Option<FileFormat> {
+        let ext = path
+            .extension()
+            .map(|v| v.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "csv" | "tsv" | "txt" => Some(FileFormat::Csv),
+            "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => Some(FileFormat::Excel),
+            "parquet" | "pq" => Some(FileFormat::Parquet),
+            "json" | "jsonl" | "ndjson" => Some(FileFormat::Json),
+            "arrow" | "ipc" | "feather" => Some(FileFormat::Ipc),
+            _ => None,
+        }
+    }
+}
+
+// 日期维度的时间粒度
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TimeGrain {
+    Day = 0,
+    // ISO 周, 周一起
+    Week = 1,
+    Month = 2,
+    Quarter = 3,
+    Year = 4,
+    // 星期几, 1~7, 周一为 1
+    Weekday = 5,
+    // 一天中的小时, 0~23, 仅日期时间列
+    Hour = 6,
+}
+impl Serialize for TimeGrain {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeGrain {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            0 => TimeGrain::Day,
+            1 => TimeGrain::Week,
+            2 => TimeGrain::Month,
+            3 => TimeGrain::Quarter,
+            4 => TimeGrain::Year,
+            5 => TimeGrain::Weekday,
+            6 => TimeGrain::Hour,
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "不支持的时间粒度 {}",
+                    value
+                )))
+            }
+        })
+    }
+}
+
+impl TimeGrain {
+    fn label(&self) -> &'static str {
+        match self {
+            TimeGrain::Day => "日",
+            TimeGrain::Week => "周",
+            TimeGrain::Month => "月",
+            TimeGrain::Quarter => "季度",
+            TimeGrain::Year => "年",
+            TimeGrain::Weekday => "星期",
+            TimeGrain::Hour => "小时",
+        }
+    }
+}
+
+// 维度, 未指定时间粒度时为列名, 否则以对象传递, 如 {"index": "day", "grain": 2}
+pub struct DimensionItem {
+    pub index: String,
+    pub grain: Option<TimeGrain>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DimensionOptions {
+    index: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grain: Option<TimeGrain>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DimensionValue {
+    Name(String),
+    Options(DimensionOptions),
+}
+
+impl Serialize for DimensionItem {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.grain {
+            None => serializer.serialize_str(&self.index),
+            Some(grain) => DimensionOptions {
+                index: self.index.clone(),
+                grain: Some(grain),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DimensionItem {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match DimensionValue::deserialize(deserializer)? {
+            DimensionValue::Name(index) => DimensionItem { index, grain: None },
+            DimensionValue::Options(v) => DimensionItem {
+                index: v.index,
+                grain: v.grain,
+            },
+        })
+    }
+}
+
+impl DimensionItem {
+    // 结果列名, 指定时间粒度时为 列名(粒度)
+    pub fn label(&self) -> String {
+        match self.grain {
+            Some(grain) => format!("{}({})", self.index, grain.label()),
+            None => self.index.clone(),
+        }
+    }
+
+    // 维度取值, 按时间粒度截断为所在周期的开始日期, 日期时间列取所在时区的日期
+    fn expr(&self, schema: &Schema) -> Result<Expr, DataboardError> {
+        let grain = match self.grain {
+            Some(v) => v,
+            None => return Ok(col(&self.index)),
+        };
+        let dtype = schema
+            .get(&self.index)
+            .ok_or_else(|| DataboardError::UnknownColumn(self.index.clone()))?;
+        let datetime = match dtype {
+            DataType::Date => false,
+            DataType::Datetime(_, _) => true,
+            _ => {
+                return Err(DataboardError::TypeMismatch(format!(
+                    "列 {} 的类型 {} 不支持按{}分组",
+                    self.index,
+                    dtype,
+                    grain.label()
+                )))
+            }
+        };
+        let target = if datetime {
+            col(&self.index).dt().date()
+        } else {
+            col(&self.index)
+        };
+        let every = match grain {
+            TimeGrain::Day => return Ok(target),
+            TimeGrain::Weekday => return Ok(target.dt().weekday()),
+            TimeGrain::Hour if datetime => return Ok(col(&self.index).dt().hour()),
+            TimeGrain::Hour => {
+                return Err(DataboardError::TypeMismatch(format!(
+                    "日期列 {} 没有时间, 不支持按小时分组",
+                    self.index
+                )))
+            }
+            TimeGrain::Week => "1w",
+            TimeGrain::Month => "1mo",
+            TimeGrain::Quarter => "1q",
+            TimeGrain::Year => "1y",
+        };
+        Ok(target.dt().truncate(lit(every), String::new()))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Dimension {
-    pub rows: Vec<String>,
-    pub columns: Vec<String>,
+    pub rows: Vec<DimensionItem>,
+    pub columns: Vec<DimensionItem>,
+}
+
+// 分组前整理的维度: 行列维度的结果列名, 以及时间粒度维度截断后的列
+struct DimensionPlan {
+    rows: Vec<String>,
+    columns: Vec<String>,
+    buckets: Vec<Expr>,
+}
+
+impl Dimension {
+    // 时间粒度维度的结果列名不能与已有的列重名, 否则会覆盖原列
+    fn prepare(&self, schema: &Schema) -> Result<DimensionPlan, DataboardError> {
+        let mut buckets = Vec::<Expr>::default();
+        let mut names = Vec::<String>::default();
+        for ele in self.rows.iter().chain(self.columns.iter()) {
+            let name = ele.label();
+            if ele.grain.is_none() || names.contains(&name) {
+                continue;
+            }
+            if schema.contains(&name) {
+                return Err(DataboardError::InvalidQuery(format!(
+                    "维度 {} 与已有的列重名",
+                    name
+                )));
+            }
+            buckets.push(ele.expr(schema)?.alias(&name));
+            names.push(name);
+        }
+        Ok(DimensionPlan {
+            rows: self.rows.iter().map(|v| v.label()).collect(),
+            columns: self.columns.iter().map(|v| v.label()).collect(),
+            buckets,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -85,6 +499,19 @@ impl<'de> Deserialize<'de> for MetricMode {
     }
 }
 
+impl MetricMode {
+    fn label(&self) -> &'static str {
+        match self {
+            MetricMode::Sum => "合计",
+            MetricMode::Count => "计数",
+            MetricMode::Max => "最大值",
+            MetricMode::Min => "最小值",
+            MetricMode::Avg => "平均",
+            MetricMode::Rate => "占比",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum FilterMode {
     Single = 0,
@@ -92,6 +519,22 @@ pub enum FilterMode {
     MatchPrefix = 2,
     DateRange = 3,
     DigitalRange = 4,
+    // 包含任一值
+    Contains = 5,
+    // 不包含任何值
+    NotContains = 6,
+    // 正则匹配
+    Regex = 7,
+    // 以任一值结尾
+    EndsWith = 8,
+    NotEqual = 9,
+    NotIn = 10,
+    IsNull = 11,
+    IsNotNull = 12,
+    GreaterThan = 13,
+    GreaterEqual = 14,
+    LessThan = 15,
+    LessEqual = 16,
 }
 impl Serialize for FilterMode {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -115,14 +558,67 @@ impl<'de> Deserialize<'de> for FilterMode {
             2 => FilterMode::MatchPrefix,
             3 => FilterMode::DateRange,
             4 => FilterMode::DigitalRange,
+            5 => FilterMode::Contains,
+            6 => FilterMode::NotContains,
+            7 => FilterMode::Regex,
+            8 => FilterMode::EndsWith,
+            9 => FilterMode::NotEqual,
+            10 => FilterMode::NotIn,
+            11 => FilterMode::IsNull,
+            12 => FilterMode::IsNotNull,
+            13 => FilterMode::GreaterThan,
+            14 => FilterMode::GreaterEqual,
+            15 => FilterMode::LessThan,
+            16 => FilterMode::LessEqual,
             _ => FilterMode::MatchPrefix,
         })
     }
 }
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum RateBase {
+    #[default]
+    Total = 0,
+    Row = 1,
+    Column = 2,
+}
+impl Serialize for RateBase {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let value = *self as i32;
+        serializer.serialize_i32(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for RateBase {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => RateBase::Row,
+            2 => RateBase::Column,
+            _ => RateBase::Total,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Metric {
     pub index: String,
     pub mode: MetricMode,
+    // 占比的分母, 仅透视表支持行合计/列合计
+    #[serde(default)]
+    pub rate: RateBase,
+}
+
+impl Metric {
+    // 指标结果列名, 如 amount(合计), 同一列的不同计算方法不会重名
+    pub fn label(&self) -> String {
+        format!("{}({})", self.index, self.mode.label())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -135,6 +631,150 @@ pub struct FilterItem {
     pub index: String,
     pub mode: FilterMode,
     pub value: Vec<String>,
+    // 文本比较是否忽略大小写
+    #[serde(default)]
+    pub ignore_case: bool,
+    // 相对日期, 仅用于 DateRange, 设置后忽略 value
+    #[serde(default)]
+    pub relative: Option<RelativeDate>,
+}
+
+// 相对于基准日期的日期范围, 均包含基准日期当天
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RelativeRange {
+    Today = 0,
+    Yesterday = 1,
+    // 最近 7 天
+    Last7Days = 2,
+    // 最近 30 天
+    Last30Days = 3,
+    // 本周(周一起)至今
+    WeekToDate = 4,
+    // 本月至今
+    MonthToDate = 5,
+    // 本季度至今
+    QuarterToDate = 6,
+    // 本年至今
+    YearToDate = 7,
+}
+impl Serialize for RelativeRange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for RelativeRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            0 => RelativeRange::Today,
+            1 => RelativeRange::Yesterday,
+            2 => RelativeRange::Last7Days,
+            3 => RelativeRange::Last30Days,
+            4 => RelativeRange::WeekToDate,
+            5 => RelativeRange::MonthToDate,
+            6 => RelativeRange::QuarterToDate,
+            7 => RelativeRange::YearToDate,
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "不支持的相对日期 {}",
+                    value
+                )))
+            }
+        })
+    }
+}
+
+// 相对日期范围的对比期
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum PeriodShift {
+    // 当期
+    #[default]
+    Current = 0,
+    // 上一期, 如上月同一时段, 最近 7 天的前 7 天
+    Previous = 1,
+    // 去年同期
+    LastYear = 2,
+}
+impl Serialize for PeriodShift {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for PeriodShift {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => PeriodShift::Previous,
+            2 => PeriodShift::LastYear,
+            _ => PeriodShift::Current,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelativeDate {
+    pub range: RelativeRange,
+    #[serde(default)]
+    pub shift: PeriodShift,
+}
+
+// 条件组内各条件的组合方式
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum FilterLogic {
+    // 全部满足
+    #[default]
+    And = 0,
+    // 任一满足
+    Or = 1,
+    // 不满足全部条件
+    Not = 2,
+}
+impl Serialize for FilterLogic {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for FilterLogic {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => FilterLogic::Or,
+            2 => FilterLogic::Not,
+            _ => FilterLogic::And,
+        })
+    }
+}
+
+// 条件组, 条件和子组按 logic 组合, 可以嵌套
+#[derive(Serialize, Deserialize)]
+pub struct FilterGroup {
+    #[serde(default)]
+    pub logic: FilterLogic,
+    #[serde(default)]
+    pub items: Vec<FilterItem>,
+    #[serde(default)]
+    pub groups: Vec<FilterGroup>,
 }
 #[derive(Serialize, Deserialize)]
 pub struct Rule {
@@ -142,9 +782,102 @@ pub struct Rule {
     pub calc: String,
 }
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum JoinMode {
+    Inner = 0,
+    Left = 1,
+    Outer = 2,
+    Semi = 3,
+    Anti = 4,
+}
+impl Serialize for JoinMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for JoinMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Ok(match value {
+            1 => JoinMode::Left,
+            2 => JoinMode::Outer,
+            3 => JoinMode::Semi,
+            4 => JoinMode::Anti,
+            _ => JoinMode::Inner,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JoinSetting {
+    // 左侧数据集
+    pub left: String,
+    // 右侧数据集
+    pub right: String,
+    // 左侧关联列
+    pub left_on: Vec<String>,
+    // 右侧关联列, 为空时与左侧相同
+    #[serde(default)]
+    pub right_on: Vec<String>,
+    pub mode: JoinMode,
+    // 右侧重名列的后缀
+    #[serde(default)]
+    pub suffix: Option<String>,
+    // 生成的数据集名称
+    pub target: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Setting {
     pub columns: HashMap<String, ColumnType>,
+    // 各列转换失败时的处理方式, 未配置时置为空值
+    #[serde(default)]
+    pub errors: HashMap<String, FailurePolicy>,
+    // 各列文本的解析配置: 日期格式/小数点/千分位等, 未配置时使用内置规则
+    #[serde(default)]
+    pub parse: HashMap<String, ParseOptions>,
+    // 类型转换之后的清洗步骤, 按顺序执行, 使用原列名
+    #[serde(default)]
+    pub cleaning: Vec<CleanStep>,
+    // 清洗之后的列操作: 拆分/合并/删除/重命名/排序
+    #[serde(default)]
+    pub operations: ColumnOperations,
+}
+
+// 单列的转换统计
+#[derive(Serialize)]
+pub struct ColumnReport {
+    pub name: String,
+    // 转换成功的值
+    pub converted: usize,
+    // 原始数据为空的值
+    pub null: usize,
+    // 转换失败后使用默认值的值
+    pub defaulted: usize,
+    // 转换失败的值, 按配置置空或排除所在行
+    pub failed: usize,
+    // 转换失败的原始值示例
+    pub samples: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct EtlReport {
+    // 原始数据行数
+    pub rows: usize,
+    // 因转换失败排除的行数
+    pub rejected: usize,
+    pub columns: Vec<ColumnReport>,
+    // 各清洗步骤影响的行数
+    pub cleaning: Vec<CleanReport>,
+    // 配置后数据的列名, 按列顺序
+    pub output: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -154,468 +887,837 @@ pub struct Query {
     pub filters: Vec<Filter>,
     pub rules: Vec<Rule>,
     pub search: Vec<FilterItem>,
+    // 条件树, 与 search 之间为且的关系
+    #[serde(default)]
+    pub condition: Option<FilterGroup>,
+    // 相对日期的基准日期, yyyy-mm-dd, 默认为当天
+    #[serde(default)]
+    pub as_of: Option<String>,
 }
 
-pub struct StateStore {
-    // 原始数据
-    records: DataFrame,
-    // 标准化数据
-    standard: DataFrame,
-    // 当前查询数据
-    result: DataFrame,
+#[derive(Default)]
+pub struct Dataset {
+    // 数据来源
+    source: String,
+    // 原始数据的读取计划, 文件在查询时按需扫描
+    records: LazyFrame,
+    // 原始数据行数, 首次使用时统计
+    rows: Option<usize>,
+    // 标准化数据的计划, 即原始数据加类型转换
+    standard: Option<LazyFrame>,
+    // 当前查询结果, 明细查询只保留计划按页读取, 聚合结果已计算完成
+    result: Arc<SearchResult>,
+    // 加入数据集列表时的序号, 锁外计算完成后据此确认数据集未被替换
+    version: u64,
 }
-impl StateStore {
-    pub fn default() -> StateStore {
-        return StateStore {
-            records: DataFrame::default(),
-            standard: DataFrame::default(),
-            result: DataFrame::default(),
-        };
+// 分页读取时缓存的结果行数上限
+const PAGE_CACHE_ROWS: usize = 100_000;
+
+#[derive(Default)]
+struct PageCache {
+    // 结果的前若干行
+    frame: DataFrame,
+    // 已读取全部结果
+    complete: bool,
+}
+
+// 查询结果, 分页读取时缓存已读取的前若干行
+#[derive(Default)]
+pub struct SearchResult {
+    plan: LazyFrame,
+    cache: Mutex<PageCache>,
+}
+
+impl SearchResult {
+    pub fn new(plan: LazyFrame) -> SearchResult {
+        SearchResult {
+            plan,
+            cache: Mutex::default(),
+        }
+    }
+
+    // 读取一页结果, 缓存不足时按翻倍的行数从头读取, 翻页的总读取量约为已读行数的两倍;
+    // 切片从 0 开始, 计划中没有过滤和排序时行数限制会下推到文件扫描, 只读取所需的行.
+    // 超过缓存上限后每页按偏移读取, 需要从头执行到该页
+    pub fn page(&self, start: usize, limit: usize, job: &Job) -> Result<DataFrame, DataboardError> {
+        let mut cache = self.cache.lock().unwrap();
+        let end = start + limit;
+        if !cache.complete && end > cache.frame.height() {
+            if end > PAGE_CACHE_ROWS {
+                return Dataset::records(self.plan.clone(), start as i64, limit, job);
+            }
+            let rows = end.max(cache.frame.height() * 2).min(PAGE_CACHE_ROWS);
+            let frame = job.collect(self.prefix(rows))?;
+            cache.complete = frame.height() < rows;
+            cache.frame = frame;
+        }
+        Ok(cache.frame.slice(start as i64, limit))
+    }
+
+    // 结果的前若干行
+    fn prefix(&self, rows: usize) -> LazyFrame {
+        self.plan.clone().slice(0, rows as IdxSize)
+    }
+}
+
+// 透视表列维度合并键的临时列名
+const PIVOT_KEY: &str = "__pivot_key";
+// 转换失败时剔除行的列, 转换结果先保存到临时列, 过滤和输出共用
+const CONVERTED_PREFIX: &str = "__converted_";
+// 透视表表头层级分隔符
+const PIVOT_SEPARATOR: &str = "|";
+// 多文件合并时记录来源文件的列名
+pub const SOURCE_FILE_COLUMN: &str = "__source_file";
+
+impl Dataset {
+    // 由已有数据生成数据集
+    pub fn from_records(source: &str, records: LazyFrame) -> Dataset {
+        Dataset {
+            source: source.to_string(),
+            records,
+            ..Default::default()
+        }
+    }
+
+    // 数据集概要, 行数在加载任务中统计
+    pub fn info(&self, id: &str) -> DatasetInfo {
+        DatasetInfo {
+            id: id.to_string(),
+            source: self.source.clone(),
+            rows: self.rows.unwrap_or_default(),
+            columns: self.records.schema().map(|v| v.len()).unwrap_or_default(),
+            ready: self.standard.is_some(),
+        }
+    }
+
+    // 执行查询计划, 支持的节点使用流式引擎分批处理
+    fn collect(lf: LazyFrame) -> Result<DataFrame, DataboardError> {
+        Ok(lf.with_streaming(true).collect()?)
     }
+
+    // 设置原始数据并读取表结构, 文件不存在或格式错误时尽早报错
+    fn load(&mut self, records: LazyFrame) -> Result<(), DataboardError> {
+        records.schema().map_err(DataboardError::parse)?;
+        self.records = records;
+        self.rows = None;
+        self.standard = None;
+        self.result = Arc::default();
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!("{}", self.preview(5)?);
+        }
+        Ok(())
+    }
+
     // 读CSV文件
-    pub fn read_csv(&mut self, path: &str) -> bool {
-        self.records = CsvReadOptions::default()
-            .try_into_reader_with_file_path(Some(path.into()))
-            .unwrap()
+    pub fn read_csv(&mut self, path: &str, options: &CsvOptions) -> Result<(), DataboardError> {
+        self.load(csv::scan_csv(path, options)?)
+    }
+
+    // 读Excel工作表
+    pub fn read_excel(&mut self, path: &str, options: &ExcelOptions) -> Result<(), DataboardError> {
+        self.load(excel::read_excel(path, options)?.lazy())
+    }
+
+    // 读Parquet文件
+    pub fn read_parquet(&mut self, path: &str) -> Result<(), DataboardError> {
+        let lf = LazyFrame::scan_parquet(path, ScanArgsParquet::default())
+            .map_err(DataboardError::parse)?;
+        self.load(lf)
+    }
+
+    // 读JSON Lines文件
+    pub fn read_ndjson(&mut self, path: &str) -> Result<(), DataboardError> {
+        let lf = LazyJsonLineReader::new(path)
             .finish()
-            .unwrap();
-        log::debug!("{}", &self.records.head(Some(5)));
-        true
+            .map_err(DataboardError::parse)?;
+        self.load(lf)
+    }
+
+    // 读Arrow IPC文件
+    pub fn read_ipc(&mut self, path: &str) -> Result<(), DataboardError> {
+        let lf =
+            LazyFrame::scan_ipc(path, ScanArgsIpc::default()).map_err(DataboardError::parse)?;
+        self.load(lf)
     }
 
-    // 保存CSV文件
-    pub fn save_csv(&mut self, path: &str) -> bool {
-        let mut df = self.result.clone();
-        let mut file = std::fs::File::create(path).unwrap();
-        CsvWriter::new(&mut file).finish(&mut df).unwrap();
-        true
+    // 读文件, 未指定格式时按扩展名识别
+    pub fn read_file(
+        &mut self,
+        path: &str,
+        format: Option<FileFormat>,
+        csv: Option<CsvOptions>,
+        excel: Option<ExcelOptions>,
+    ) -> Result<(), DataboardError> {
+        self.source = path.to_string();
+        match format.unwrap_or_else(|| FileFormat::from_path(path)) {
+            FileFormat::Csv => self.read_csv(path, &csv.unwrap_or_default()),
+            FileFormat::Excel => self.read_excel(path, &excel.unwrap_or_default()),
+            FileFormat::Parquet => self.read_parquet(path),
+            FileFormat::Json => self.read_ndjson(path),
+            FileFormat::Ipc => self.read_ipc(path),
+        }
+    }
+
+    // 读多个文件并纵向合并, 路径可以是文件、目录或通配符
+    pub fn read_files(
+        &mut self,
+        paths: &[String],
+        format: Option<FileFormat>,
+        csv: Option<CsvOptions>,
+        excel: Option<ExcelOptions>,
+        source_column: bool,
+        job: &Job,
+    ) -> Result<(), DataboardError> {
+        let files = expand_paths(paths)?;
+        let base = common_parent(&files);
+
+        let mut frames = Vec::with_capacity(files.len());
+        for file in &files {
+            job.stage(JobStage::Read)?;
+            let path = file.to_string_lossy().to_string();
+            let mut data = Dataset::default();
+            data.read_file(&path, format, csv.clone(), excel.clone())
+                .map_err(|e| e.context(&path))?;
+            let mut lf = data.records;
+            if source_column {
+                let name = file
+                    .strip_prefix(&base)
+                    .unwrap_or(file)
+                    .to_string_lossy()
+                    .to_string();
+                lf = lf.with_column(lit(name).alias(SOURCE_FILE_COLUMN));
+            }
+            frames.push(lf);
+        }
+        log::debug!("union {} files", frames.len());
+
+        let mut lf = union_frames(frames)?;
+        if source_column {
+            // 来源列放在最后
+            lf = lf.select([
+                col("*").exclude([SOURCE_FILE_COLUMN]),
+                col(SOURCE_FILE_COLUMN),
+            ]);
+        }
+        self.source = paths.join("; ");
+        self.load(lf)
+    }
+
+    // 关联两个数据集的原始数据, 生成新数据集
+    pub fn join(
+        mut left: LazyFrame,
+        mut right: LazyFrame,
+        setting: &JoinSetting,
+        job: &Job,
+    ) -> Result<Dataset, DataboardError> {
+        let right_on = if setting.right_on.is_empty() {
+            setting.left_on.clone()
+        } else {
+            setting.right_on.clone()
+        };
+        if setting.left_on.is_empty() || setting.left_on.len() != right_on.len() {
+            return Err(DataboardError::InvalidQuery(
+                "关联列不能为空且左右数量需一致".to_string(),
+            ));
+        }
+        if setting.target.trim().is_empty() {
+            return Err(DataboardError::InvalidQuery(
+                "数据集名称不能为空".to_string(),
+            ));
+        }
+        let lschema = left.schema()?;
+        let rschema = right.schema()?;
+
+        // 关联列类型不一致时统一按字符串关联
+        for (l, r) in setting.left_on.iter().zip(right_on.iter()) {
+            let ltype = lschema
+                .get(l)
+                .ok_or_else(|| DataboardError::UnknownColumn(l.clone()))?;
+            let rtype = rschema
+                .get(r)
+                .ok_or_else(|| DataboardError::UnknownColumn(r.clone()))?;
+            if ltype != rtype {
+                left = left.with_column(col(l).cast(DataType::String));
+                right = right.with_column(col(r).cast(DataType::String));
+            }
+        }
+        let left_on: Vec<Expr> = setting.left_on.iter().map(|v| col(v)).collect();
+        let right_on: Vec<Expr> = right_on.iter().map(|v| col(v)).collect();
+
+        let how = match setting.mode {
+            JoinMode::Inner => JoinType::Inner,
+            JoinMode::Left => JoinType::Left,
+            JoinMode::Outer => JoinType::Outer,
+            JoinMode::Semi => JoinType::Semi,
+            JoinMode::Anti => JoinType::Anti,
+        };
+        let mut args = JoinArgs::new(how).with_coalesce(JoinCoalesce::CoalesceColumns);
+        args.suffix = setting.suffix.clone().filter(|v| !v.is_empty());
+
+        let mut lf = left.join(right, left_on, right_on, args);
+        // 流式引擎执行全连接时会丢失右侧未匹配的行, 全连接先用默认引擎计算
+        if setting.mode == JoinMode::Outer {
+            lf = job.collect_default(lf)?.lazy();
+        }
+        let source = format!("{} + {}", setting.left, setting.right);
+        Ok(Dataset::from_records(&source, lf))
+    }
+
+    // 保存查询结果, 未指定格式时按扩展名识别
+    pub fn save_file(
+        result: LazyFrame,
+        path: &str,
+        format: Option<FileFormat>,
+        job: &Job,
+    ) -> Result<(), DataboardError> {
+        let format = format.unwrap_or_else(|| FileFormat::from_path(path));
+        if format == FileFormat::Excel {
+            return Err(DataboardError::InvalidQuery(format!(
+                "不支持保存为 {:?} 格式",
+                format
+            )));
+        }
+        let mut df = job.collect(result)?;
+        job.stage(JobStage::Save)?;
+        let mut file = std::fs::File::create(path)?;
+        match format {
+            FileFormat::Csv => {
+                CsvWriter::new(&mut file).finish(&mut convert::csv_frame(&df)?)?;
+            }
+            FileFormat::Parquet => {
+                ParquetWriter::new(&mut file).finish(&mut df)?;
+            }
+            FileFormat::Json => {
+                JsonWriter::new(&mut file)
+                    .with_json_format(JsonFormat::JsonLines)
+                    .finish(&mut convert::json_frame(&df)?)?;
+            }
+            FileFormat::Ipc => {
+                IpcWriter::new(&mut file).finish(&mut df)?;
+            }
+            FileFormat::Excel => {}
+        }
+        Ok(())
     }
 
     // 标准数据行数
-    pub fn count(&mut self) -> usize {
-        self.records.shape().0
+    pub fn count(&mut self, job: &Job) -> Result<usize, DataboardError> {
+        if let Some(v) = self.rows {
+            return Ok(v);
+        }
+        let rows = job.count(self.records.clone())?;
+        self.rows = Some(rows);
+        Ok(rows)
     }
 
     // 标准数据的列
-    pub fn columns(&mut self) -> Vec<Series> {
-        let rdf = self.records.head(Some(1));
-        let ret = rdf.get_columns();
-        ret.to_vec()
+    pub fn columns(&mut self) -> Result<Vec<Series>, DataboardError> {
+        let rdf = Self::collect(self.records.clone().limit(1))?;
+        let ret = rdf
+            .get_columns()
+            .iter()
+            .map(convert::json_compatible)
+            .collect::<PolarsResult<Vec<_>>>()?;
+        Ok(ret)
     }
+    // 标准化数据的计划
+    pub fn standard(&self) -> Result<LazyFrame, DataboardError> {
+        self.standard
+            .clone()
+            .ok_or_else(|| DataboardError::InvalidQuery("请先完成数据配置".to_string()))
+    }
+
     // 获取指定列的唯一值
-    pub fn column_unique(&mut self, name: String) -> Series {
-        let ret = self.standard.column(&name).unwrap().unique().unwrap();
-        ret
+    pub fn column_unique(standard: LazyFrame, name: String) -> Result<Series, DataboardError> {
+        let target = match standard.schema()?.get(&name) {
+            // 定点小数不支持去重, 按文本处理
+            Some(DataType::Decimal(_, _)) => col(&name).cast(DataType::String),
+            Some(_) => col(&name),
+            None => return Err(DataboardError::UnknownColumn(name)),
+        };
+        let df = Self::collect(standard.select([target.unique()]))?;
+        Ok(convert::json_compatible(df.column(&name)?)?)
     }
 
     // 预览标准数据行数
-    pub fn preview(&mut self, count: usize) -> DataFrame {
-        self.records.head(Some(count))
-    }
-
-    // 数据格式标准化
-    pub fn etl(&mut self, mapping: HashMap<String, ColumnType>) -> Result<(), PolarsError> {
-        let mut new_df = DataFrame::default();
-
-        for (_index, s) in self.records.get_columns().iter().enumerate() {
-            let o_cast = mapping.get(s.name());
-            match o_cast {
-                Some(ctype) => {
-                    let transform = match s.dtype() {
-                        DataType::Int32 => match ctype {
-                            ColumnType::String => s.cast(&DataType::String),
-                            ColumnType::Float => s.cast(&DataType::Float32),
-                            ColumnType::Date => {
-                                (s * 1_000).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
-                            }
-                            _ => s.cast(s.dtype()),
-                        },
-                        DataType::Float32 | DataType::Float64 => match ctype {
-                            ColumnType::String => s.cast(&DataType::String),
-                            ColumnType::Integer => s.cast(&DataType::Int32),
-                            ColumnType::Float => s.cast(&DataType::Float32),
-                            ColumnType::Date => (s * 1_000)
-                                .cast(&DataType::Int32)?
-                                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None)),
-                        },
-                        DataType::String => match ctype {
-                            ColumnType::String => s
-                                .str()?
-                                .into_iter()
-                                .map(|op| op.map(|val| val.trim()))
-                                .collect::<StringChunked>()
-                                .cast(&DataType::String),
-                            ColumnType::Integer => {
-                                let re = Regex::new(r"\d+").unwrap();
-                                s.str()?
-                                    .into_iter()
-                                    .map(|op| {
-                                        op.and_then(|val| {
-                                            re.find(val)
-                                                .and_then(|v| v.as_str().parse::<i32>().ok())
-                                        })
-                                    })
-                                    .collect::<Int32Chunked>()
-                                    .cast(&DataType::Int32)
-                            }
-                            ColumnType::Float => {
-                                let re = Regex::new(r"\d+[\,\.\d]*").unwrap();
-                                s.str()?
-                                    .into_iter()
-                                    .map(|op| {
-                                        op.and_then(|val| {
-                                            re.find(val).and_then(|v| {
-                                                let mut s = v.as_str().replace(",", "");
-                                                if s.ends_with('.') {
-                                                    s = format!("{}0", s)
-                                                }
-                                                s.parse::<f32>().ok()
-                                            })
-                                        })
-                                    })
-                                    .collect::<Float32Chunked>()
-                                    .cast(&DataType::Float32)
-                            }
-                            ColumnType::Date => {
-                                let re_00 =
-                                    Regex::new(r"\b(\d{4})[-.](\d{2})[-.](\d{2})\b").unwrap(); //yyyy-mm-dd yyyy.mm.dd
-                                let re_01 = Regex::new(r"\b(\d{4})(\d{2})(\d{2})\b").unwrap(); //yyyymmdd
-                                let re_02 = Regex::new(r"\b(\d{2})(\d{2})(\d{2})\b").unwrap(); //yymmdd
-                                let re_03 = Regex::new(r"\b(\d{4})/(\d{2})/(\d{2})\b").unwrap(); // yyyy/mm/dd
-                                let re_04 = Regex::new(r"\b(\d{2})/(\d{2})/(\d{4})\b").unwrap(); // dd/mm/yyyy
-                                let re_05 = Regex::new(r"\b(\d{4})年(\d{2})月(\d{2})日\b").unwrap(); // yyyy年mm月dd日
-
-                                fn format_date(year: &str, month: &str, day: &str) -> String {
-                                    format!("{}-{}-{}", year, month, day)
-                                }
-
-                                s.str()?
-                                    .into_iter()
-                                    .map(|op| {
-                                        op.and_then(|val| {
-                                            if let Some(caps) = re_00.captures(val) {
-                                                return Some(format_date(
-                                                    &caps[1], &caps[2], &caps[3],
-                                                ));
-                                            } else if let Some(caps) = re_01.captures(val) {
-                                                return Some(format_date(
-                                                    &caps[1], &caps[2], &caps[3],
-                                                ));
-                                            } else if let Some(caps) = re_02.captures(val) {
-                                                return Some(format!(
-                                                    "20{}",
-                                                    format_date(&caps[1], &caps[2], &caps[3])
-                                                ));
-                                            } else if let Some(caps) = re_03.captures(val) {
-                                                return Some(format_date(
-                                                    &caps[1], &caps[2], &caps[3],
-                                                ));
-                                            } else if let Some(caps) = re_04.captures(val) {
-                                                return Some(format_date(
-                                                    &caps[3], &caps[2], &caps[1],
-                                                ));
-                                            } else if let Some(caps) = re_05.captures(val) {
-                                                return Some(format_date(
-                                                    &caps[1], &caps[2], &caps[3],
-                                                ));
-                                            } else {
-                                                return Some("2000-01-01".to_string());
-                                            }
-                                        })
-                                    })
-                                    .collect::<StringChunked>()
-                                    .as_date(Some("%Y-%m-%d"), true)?
-                                    .cast(&DataType::Date)
-                            }
-                        },
-                        DataType::Date => match ctype {
-                            ColumnType::String => s.cast(&DataType::String),
-                            ColumnType::Integer => s.cast(&DataType::Int32),
-                            ColumnType::Float => s.cast(&DataType::Float32),
-                            _ => s.cast(s.dtype()),
-                        },
-                        DataType::Boolean => match ctype {
-                            ColumnType::String => s.cast(&DataType::String),
-                            ColumnType::Integer => s.cast(&DataType::Int8),
-                            ColumnType::Float => s.cast(&DataType::Float32),
-                            _ => s.cast(s.dtype()),
-                        },
-                        _ => match ctype {
-                            ColumnType::String => s.cast(&DataType::String),
-                            ColumnType::Integer => s.cast(&DataType::Int32),
-                            ColumnType::Float => s.cast(&DataType::Float32),
-                            _ => s.cast(s.dtype()),
-                        },
-                    };
-                    match transform {
-                        Ok(mut t) => {
-                            let col = t.rename(s.name());
-                            match new_df.with_column(col.clone()) {
-                                Ok(r) => new_df = r.clone(),
-                                Err(_) => todo!(),
-                            }
-                        }
-                        Err(_) => todo!(),
-                    }
-                }
+    pub fn preview(&mut self, count: usize) -> Result<DataFrame, DataboardError> {
+        Self::collect(self.records.clone().limit(count as IdxSize))
+    }
+
+    // 转换失败时填充的类型默认值
+    // 文本类型不会转换失败, 没有默认值
+    fn default_value(ctype: &ColumnType, output: &DataType) -> Option<Expr> {
+        let value = match ctype {
+            ColumnType::String | ColumnType::Categorical => return None,
+            ColumnType::Boolean => lit(false),
+            ColumnType::Date | ColumnType::Datetime(_, _) => {
+                lit(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap())
+            }
+            ColumnType::Float | ColumnType::Float64 => lit(0f64),
+            _ => lit(0i64),
+        };
+        Some(value.cast(output.clone()))
+    }
+
+    // 转换失败的原始值示例
+    fn failed_samples(
+        records: LazyFrame,
+        name: &str,
+        failed: Expr,
+        job: &Job,
+    ) -> Result<Vec<String>, DataboardError> {
+        let df = job.collect(
+            records
+                .filter(failed)
+                .select([col(name).cast(DataType::String)])
+                .limit(100),
+        )?;
+        let mut samples = Vec::<String>::default();
+        for v in df.column(name)?.str()?.into_iter().flatten() {
+            if samples.len() < 5 && !samples.iter().any(|s| s == v) {
+                samples.push(v.to_string());
+            }
+        }
+        Ok(samples)
+    }
+
+    // 原始数据的计划和已统计的行数, 供锁外执行数据配置
+    pub fn source(&self) -> (LazyFrame, Option<usize>) {
+        (self.records.clone(), self.rows)
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set_rows(&mut self, rows: usize) {
+        self.rows = Some(rows);
+    }
+
+    // 保存数据配置的结果
+    pub fn set_standard(&mut self, standard: LazyFrame, rows: usize) {
+        self.standard = Some(standard);
+        self.rows = Some(rows);
+    }
+
+    // 数据格式标准化, 类型转换加入查询计划, 查询时按批执行; 同时统计全部数据的转换结果
+    // 不持有数据集, 可以在锁外执行, 返回标准化数据的计划和转换结果
+    pub fn etl(
+        records: LazyFrame,
+        rows: Option<usize>,
+        setting: Setting,
+        job: &Job,
+    ) -> Result<(LazyFrame, EtlReport), DataboardError> {
+        let schema = records.schema()?;
+        let mut exprs = Vec::<Expr>::default();
+        let mut flags = Vec::<Expr>::default();
+        let mut stats = Vec::<Expr>::default();
+        let mut rejects = Vec::<Expr>::default();
+        let mut staged = Vec::<Expr>::default();
+        let mut kept = Vec::<Expr>::default();
+        let mut selected = Vec::<Expr>::default();
+        let mut columns = Vec::default();
+        for (i, (name, dtype)) in schema.iter().enumerate() {
+            let ctype = match setting.columns.get(name.as_str()) {
+                Some(v) => v.clone(),
                 None => {
-                    return Err(PolarsError::InvalidOperation("".into()));
+                    return Err(DataboardError::InvalidQuery(format!(
+                        "列 {} 未配置类型",
+                        name
+                    )));
                 }
+            };
+            let policy = setting
+                .errors
+                .get(name.as_str())
+                .copied()
+                .unwrap_or_default();
+            let options = setting
+                .parse
+                .get(name.as_str())
+                .cloned()
+                .unwrap_or_default();
+            options
+                .validate()
+                .map_err(|e| DataboardError::InvalidQuery(format!("列 {} {}", name, e)))?;
+            // 用空列推算转换后的类型, 供后续查询计划使用
+            let output = Self::transform(&Series::new_empty(name, dtype), &ctype, &options)
+                .map_err(|e| DataboardError::TypeMismatch(format!("列 {} 转换失败: {}", name, e)))?
+                .dtype()
+                .clone();
+            let column = name.to_string();
+            let target = ctype.clone();
+            let converted = col(name).map(
+                move |s| {
+                    Self::transform(&s, &target, &options)
+                        .map(Some)
+                        .map_err(|e| polars_err!(InvalidOperation: "列 {} 转换失败: {}", column, e))
+                },
+                GetOutput::from_type(output.clone()),
+            );
+            // 原始值不为空而转换结果为空即转换失败
+            let failed = col(name).is_not_null().and(converted.clone().is_null());
+
+            // 统计时先逐行标记, 每列只转换一次
+            let null_flag = format!("null_{}", i);
+            let failed_flag = format!("failed_{}", i);
+            flags.push(col(name).is_null().alias(&null_flag));
+            flags.push(failed.clone().alias(&failed_flag));
+            stats.push(col(&null_flag).sum());
+            stats.push(col(&failed_flag).sum());
+            let expr = match (policy, Self::default_value(&ctype, &output)) {
+                (FailurePolicy::Default, Some(value)) => when(failed.clone())
+                    .then(value)
+                    .otherwise(converted.clone())
+                    .alias(name),
+                _ => converted.clone(),
+            };
+            if policy == FailurePolicy::Reject {
+                let temp = format!("{}{}", CONVERTED_PREFIX, i);
+                rejects.push(col(&failed_flag));
+                kept.push(col(name).is_null().or(col(&temp).is_not_null()));
+                staged.push(converted.alias(&temp));
+                selected.push(col(&temp).alias(name));
+            } else {
+                selected.push(expr.clone());
             }
+            exprs.push(expr);
+            columns.push((name.to_string(), policy, failed));
         }
-        self.standard = new_df;
-        Ok(())
+        // 先转换少量数据, 配置有误时尽早报错
+        let cleaned = clean::plan(&setting.cleaning, records.clone().select(exprs))?;
+        let sample = setting.operations.apply(cleaned.records)?;
+        // 去重/截断等步骤无法下推行数限制, 可能扫描全部数据, 需要可以取消
+        let output = job
+            .collect(sample.limit(100))?
+            .get_column_names()
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect();
+
+        let rows = match rows {
+            Some(v) => v,
+            None => job.count(records.clone())?,
+        };
+        let reject = rejects.into_iter().reduce(|a, b| a.or(b));
+        if let Some(v) = &reject {
+            stats.push(v.clone().sum().alias("rejected"));
+        }
+        let df = job.collect(records.clone().select(flags).select(stats))?;
+        let value = |name: &str| -> Result<usize, DataboardError> {
+            let v = df.column(name)?.cast(&DataType::UInt64)?;
+            Ok(v.u64()?.get(0).unwrap_or(0) as usize)
+        };
+
+        let mut report = EtlReport {
+            rows,
+            rejected: 0,
+            columns: Vec::default(),
+            cleaning: Vec::default(),
+            output,
+        };
+        if reject.is_some() {
+            report.rejected = value("rejected")?;
+        }
+        for (i, (name, policy, failed)) in columns.into_iter().enumerate() {
+            let null = value(&format!("null_{}", i))?;
+            let count = value(&format!("failed_{}", i))?;
+            let samples = if count > 0 {
+                Self::failed_samples(records.clone(), &name, failed, job)?
+            } else {
+                Vec::default()
+            };
+            if count > 0 && policy == FailurePolicy::Abort {
+                return Err(DataboardError::TypeMismatch(format!(
+                    "列 {} 有 {} 个值无法转换, 例如: {}",
+                    name,
+                    count,
+                    samples.join(", ")
+                )));
+            }
+            let defaulted = if policy == FailurePolicy::Default {
+                count
+            } else {
+                0
+            };
+            report.columns.push(ColumnReport {
+                name,
+                converted: rows - null - count,
+                null,
+                defaulted,
+                failed: count - defaulted,
+                samples,
+            });
+        }
+
+        // 剔除转换失败的行: 转换结果写入临时列, 按临时列过滤后输出, 每列只转换一次
+        let mut standard = records;
+        if let Some(v) = kept.into_iter().reduce(|a, b| a.and(b)) {
+            standard = standard.with_columns(staged).filter(v);
+        }
+        let cleaned = clean::plan(&setting.cleaning, standard.select(selected))?;
+        let records = cleaned.records.clone();
+        report.cleaning = cleaned.count(job)?;
+        Ok((setting.operations.apply(records)?, report))
     }
 
-    // 数据处理
-    pub fn search(&mut self, query: Query) -> DataFrame {
-        let mut df = self.standard.clone();
-
-        let custom_filter = |cdf: &DataFrame| {
-            //第一列数据不能为空
-            let mut filter = cdf.get_columns()[0].is_not_null();
-
-            for ele in query.search {
-                let sr = cdf.column(ele.index.as_str());
-                match sr {
-                    Ok(s) => match s.dtype() {
-                        DataType::Int32 => {
-                            let vr: Result<Vec<i32>, _> =
-                                ele.value.into_iter().map(|s| s.parse::<i32>()).collect();
-                            match vr {
-                                Ok(v) => {
-                                    if v.len() == 0 {
-                                        continue;
-                                    }
-                                    match ele.mode {
-                                        FilterMode::Single | FilterMode::MatchPrefix => {
-                                            let m = v.get(0).unwrap();
-                                            let cp = s.equal(*m);
-
-                                            filter = filter & (cp.unwrap());
-                                        }
-                                        FilterMode::Multi => {
-                                            let vs: Result<ChunkedArray<BooleanType>, PolarsError> =
-                                                is_in(s, &Series::new("cm", v));
-                                            match vs {
-                                                Ok(s) => {
-                                                    filter = filter & s;
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        FilterMode::DateRange => {
-                                            if v.len() > 1 {
-                                                let sv = v.get(0).unwrap();
-                                                let ev = v.get(1).unwrap();
-                                                let cp =
-                                                    s.gt_eq(*sv).unwrap() & s.lt_eq(*ev).unwrap();
-                                                filter = filter & cp;
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                Err(_) => {}
-                            }
-                        }
-                        DataType::Float32 => {
-                            let vr: Result<Vec<f32>, _> =
-                                ele.value.into_iter().map(|s| s.parse::<f32>()).collect();
-                            match vr {
-                                Ok(v) => {
-                                    if v.len() == 0 {
-                                        continue;
-                                    }
-                                    match ele.mode {
-                                        FilterMode::DigitalRange => {
-                                            if v.len() > 1 {
-                                                let sv = v.get(0).unwrap();
-                                                let ev = v.get(1).unwrap();
-                                                let cp =
-                                                    s.gt_eq(*sv).unwrap() & s.lt_eq(*ev).unwrap();
-                                                filter = filter & cp;
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                Err(_) => {}
-                            }
-                        }
-                        DataType::String => {
-                            let v: Vec<String> = ele
-                                .value
-                                .into_iter()
-                                .map(|s| s.trim().to_string())
-                                .collect();
-
-                            if v.len() == 0 {
-                                continue;
-                            }
-                            match ele.mode {
-                                FilterMode::Single => {
-                                    let m = v.get(0).unwrap();
-                                    let r = s
-                                        .str()
-                                        .unwrap()
-                                        .into_iter()
-                                        .map(|v| {
-                                            v.and_then(|v| {
-                                                let cp = v.eq(m);
-                                                Some(cp)
-                                            })
-                                        })
-                                        .collect::<BooleanChunked>();
-                                    filter = filter & r;
-                                }
-                                FilterMode::Multi => {
-                                    let vs: Result<ChunkedArray<BooleanType>, PolarsError> =
-                                        is_in(s, &Series::new("cm", v));
-                                    match vs {
-                                        Ok(s) => {
-                                            filter = filter & s;
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                FilterMode::MatchPrefix => {
-                                    let m = v.get(0).unwrap();
-                                    let r = s
-                                        .str()
-                                        .unwrap()
-                                        .into_iter()
-                                        .map(|v| {
-                                            v.and_then(|v| {
-                                                let cp = v.starts_with(m.as_str());
-                                                Some(cp)
-                                            })
-                                        })
-                                        .collect::<BooleanChunked>();
-                                    filter = filter & r;
-                                }
-                                _ => {}
-                            }
-                        }
-                        DataType::Date => {
-                            let v: Vec<String> = ele
-                                .value
-                                .into_iter()
-                                .map(|s| s.trim().to_string())
-                                .collect();
-                            let sv = s.date().unwrap().strftime("%Y-%m-%d");
-                            if v.len() == 0 {
-                                continue;
-                            }
-                            match ele.mode {
-                                FilterMode::Single => {
-                                    let m = v.get(0).unwrap();
-                                    let r = sv
-                                        .into_iter()
-                                        .map(|v| {
-                                            v.and_then(|v| {
-                                                let cp = v.eq(m);
-                                                Some(cp)
-                                            })
-                                        })
-                                        .collect::<BooleanChunked>();
-                                    filter = filter & r;
-                                }
-                                FilterMode::Multi => {
-                                    let vs: Result<ChunkedArray<BooleanType>, PolarsError> =
-                                        is_in(&sv.into_series(), &Series::new("cm", v));
-                                    match vs {
-                                        Ok(s) => {
-                                            filter = filter & s;
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                FilterMode::MatchPrefix => {
-                                    let sv = v.get(0).unwrap();
-                                    let ev = v.get(1).unwrap();
-                                    let arg = DateChunked::parse_from_str_slice(
-                                        "dt",
-                                        &[sv, ev],
-                                        "%Y-%m-%d",
-                                    );
-                                    let isv = arg.get(0).unwrap();
-                                    let iev = arg.get(1).unwrap();
-
-                                    let cp = s.gt_eq(isv).unwrap() & s.lt_eq(iev).unwrap();
-                                    filter = filter & cp;
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ => {}
-                    },
-                    Err(_) => {}
+    // 按配置类型转换一列数据
+    fn transform(s: &Series, ctype: &ColumnType, options: &ParseOptions) -> PolarsResult<Series> {
+        let transform = match ctype {
+            ColumnType::Boolean => convert::to_boolean(s),
+            ColumnType::Datetime(unit, tz) => {
+                convert::to_datetime(s, *unit, tz.as_deref(), options)
+            }
+            ColumnType::Duration(unit) => convert::to_duration(s, *unit),
+            ColumnType::Int64 => convert::to_int64(s, options),
+            ColumnType::Float64 => convert::to_float64(s, options),
+            ColumnType::Decimal(precision, scale) => {
+                convert::to_decimal(s, *precision, *scale, options)
+            }
+            ColumnType::Categorical => convert::to_categorical(s),
+            _ => Self::transform_basic(s, ctype, options),
+        };
+        let mut ret = transform?;
+        ret.rename(s.name());
+        Ok(ret)
+    }
+
+    // 字符串/整数/小数/日期的转换
+    fn transform_basic(
+        s: &Series,
+        ctype: &ColumnType,
+        options: &ParseOptions,
+    ) -> PolarsResult<Series> {
+        match s.dtype() {
+            DataType::Int32 => match ctype {
+                ColumnType::String => s.cast(&DataType::String),
+                ColumnType::Float => s.cast(&DataType::Float32),
+                ColumnType::Date => {
+                    (s * 1_000).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                }
+                _ => s.cast(s.dtype()),
+            },
+            DataType::Float32 | DataType::Float64 => match ctype {
+                ColumnType::String => s.cast(&DataType::String),
+                ColumnType::Integer => s.cast(&DataType::Int32),
+                ColumnType::Float => s.cast(&DataType::Float32),
+                ColumnType::Date => (s * 1_000)
+                    .cast(&DataType::Int32)?
+                    .cast(&DataType::Datetime(TimeUnit::Milliseconds, None)),
+                _ => s.cast(s.dtype()),
+            },
+            DataType::String => match ctype {
+                ColumnType::String => s
+                    .str()?
+                    .into_iter()
+                    .map(|op| op.map(|val| val.trim()))
+                    .collect::<StringChunked>()
+                    .cast(&DataType::String),
+                ColumnType::Integer => s
+                    .str()?
+                    .into_iter()
+                    .map(|op| op.and_then(|v| options.integer(v)))
+                    .collect::<Int32Chunked>()
+                    .cast(&DataType::Int32),
+                ColumnType::Float => s
+                    .str()?
+                    .into_iter()
+                    .map(|op| op.and_then(|v| options.float(v)))
+                    .collect::<Float32Chunked>()
+                    .cast(&DataType::Float32),
+                ColumnType::Date => {
+                    let dates: Vec<Option<NaiveDate>> = s
+                        .str()?
+                        .into_iter()
+                        .map(|op| op.and_then(|v| options.date(v)))
+                        .collect();
+                    Series::new(s.name(), dates).cast(&DataType::Date)
                 }
+                _ => s.cast(s.dtype()),
+            },
+            DataType::Date => match ctype {
+                ColumnType::String => s.cast(&DataType::String),
+                ColumnType::Integer => s.cast(&DataType::Int32),
+                ColumnType::Float => s.cast(&DataType::Float32),
+                _ => s.cast(s.dtype()),
+            },
+            DataType::Boolean => match ctype {
+                ColumnType::String => s.cast(&DataType::String),
+                ColumnType::Integer => s.cast(&DataType::Int8),
+                ColumnType::Float => s.cast(&DataType::Float32),
+                _ => s.cast(s.dtype()),
+            },
+            _ => match ctype {
+                ColumnType::String => s.cast(&DataType::String),
+                ColumnType::Integer => s.cast(&DataType::Int32),
+                ColumnType::Float => s.cast(&DataType::Float32),
+                _ => s.cast(s.dtype()),
+            },
+        }
+    }
+
+    // 计算规则生成新列
+    fn apply_rules(mut lf: LazyFrame, rules: &[Rule]) -> Result<LazyFrame, DataboardError> {
+        for rule in rules {
+            if rule.name.trim().is_empty() || rule.calc.trim().is_empty() {
+                continue;
             }
-            filter
+            let expr = formula::parse(&rule.calc)
+                .map_err(|e| DataboardError::Parse(format!("规则 {} {}", rule.name, e)))?;
+            // 逐个添加, 后面的规则可以引用前面规则生成的列
+            lf = lf.with_column(expr.alias(rule.name.trim()));
+        }
+        Ok(lf)
+    }
+
+    // 占比指标的基础聚合: 数值列累和, 其他列计数
+    fn rate_agg(schema: &Schema, index: &str) -> Expr {
+        match schema.get(index) {
+            Some(dtype) if dtype.is_numeric() => col(index).sum(),
+            _ => col(index).count(),
+        }
+    }
+
+    // 透视结果转换为占比
+    fn pivot_rate(
+        df: DataFrame,
+        index: &[String],
+        base: RateBase,
+    ) -> Result<DataFrame, DataboardError> {
+        let values: Vec<String> = df
+            .get_column_names()
+            .into_iter()
+            .filter(|name| !index.iter().any(|v| v == name))
+            .map(|name| name.to_string())
+            .collect();
+        if values.is_empty() {
+            return Ok(df);
+        }
+        let total = match base {
+            RateBase::Total => values.iter().map(|v| col(v).sum()).reduce(|a, b| a + b),
+            RateBase::Row => values
+                .iter()
+                .map(|v| col(v).fill_null(lit(0)))
+                .reduce(|a, b| a + b),
+            RateBase::Column => None,
         };
+        let exprs: Vec<Expr> = values
+            .iter()
+            .map(|v| {
+                let denominator = match &total {
+                    Some(t) => t.clone(),
+                    None => col(v).sum(),
+                };
+                binary_expr(col(v), Operator::TrueDivide, denominator).alias(v)
+            })
+            .collect();
+        Ok(df.lazy().with_columns(exprs).collect()?)
+    }
 
-        df = df.filter(&custom_filter(&df)).unwrap();
-        // todo:透视表暂只支持单列单行单值
-        if query.dimensions.columns.len() > 0 {
-            if query.dimensions.rows.len() > 0 {
-                let d_row = &query.dimensions.rows[0..1];
-                let d_col = &query.dimensions.columns[0..1];
+    // 数据处理, 规则/过滤/聚合组合为一个查询计划, 明细查询只保留计划, 聚合结果计算完成
+    // 不持有数据集, 可以在锁外执行
+    pub fn search(
+        standard: LazyFrame,
+        query: Query,
+        job: &Job,
+    ) -> Result<LazyFrame, DataboardError> {
+        let mut lf = Self::apply_rules(standard, &query.rules)?;
+        let schema = lf.schema()?;
+        if let Some(expr) = filter::compile(&schema, &query)? {
+            lf = lf.filter(expr);
+        }
+        // 时间粒度维度在分组前截断
+        let DimensionPlan {
+            rows: d_row,
+            columns: d_column,
+            buckets,
+        } = query.dimensions.prepare(&schema)?;
+        if !buckets.is_empty() {
+            lf = lf.with_columns(buckets);
+        }
+        let mut labels = Vec::<String>::default();
+        for ele in query.metrics.iter() {
+            let label = ele.label();
+            if labels.contains(&label) {
+                return Err(DataboardError::InvalidQuery(format!("指标 {} 重复", label)));
+            }
+            labels.push(label);
+        }
+        // 透视表
+        if !d_column.is_empty() {
+            if !d_row.is_empty() {
+                let d_row = &d_row;
+                // 多个列维度合并为一个表头键, 如 2023|Q1
+                let keys: Vec<Expr> = d_column
+                    .iter()
+                    .map(|v| col(v).cast(DataType::String).fill_null(lit("")))
+                    .collect();
+                let source = job.collect(
+                    lf.clone()
+                        .with_column(concat_str(keys, PIVOT_SEPARATOR, false).alias(PIVOT_KEY)),
+                )?;
+                let multi = query.metrics.len() > 1;
+
+                let mut pivot_df: Option<DataFrame> = None;
                 for ele in query.metrics {
-                    let epx: Expr;
-                    match ele.mode {
-                        MetricMode::Sum => {
-                            epx = col(&ele.index).sum();
-                        }
-                        MetricMode::Max => {
-                            epx = col(&ele.index).max();
-                        }
-                        MetricMode::Min => {
-                            epx = col(&ele.index).min();
-                        }
-                        MetricMode::Avg => {
-                            epx = col(&ele.index).mean();
-                        }
-                        MetricMode::Rate => todo!(),
-                        _ => {
-                            epx = col(&ele.index).count();
-                        }
-                    }
-                    let pivot_df = pivot::pivot(
-                        &df,
+                    let epx = match ele.mode {
+                        MetricMode::Sum => col(&ele.index).sum(),
+                        MetricMode::Max => col(&ele.index).max(),
+                        MetricMode::Min => col(&ele.index).min(),
+                        MetricMode::Avg => col(&ele.index).mean(),
+                        MetricMode::Rate => Self::rate_agg(&source.schema(), &ele.index),
+                        _ => col(&ele.index).count(),
+                    };
+                    let mut v = pivot::pivot_stable(
+                        &source,
                         d_row,
-                        d_col,
-                        Some(vec![ele.index]),
-                        false,
+                        [PIVOT_KEY],
+                        Some([ele.index.as_str()]),
+                        true,
                         Some(epx),
-                        Option::default(),
-                    );
-                    match pivot_df {
-                        Ok(v) => {
-                            df = v
-                                .sort(
-                                    d_row,
-                                    SortMultipleOptions::new().with_order_descending(false),
-                                )
-                                .unwrap();
-                            break;
+                        Some(PIVOT_SEPARATOR),
+                    )?;
+                    if ele.mode == MetricMode::Rate {
+                        v = Self::pivot_rate(v, d_row, ele.rate)?;
+                    }
+                    // 多指标时表头为 指标|列维度值
+                    let values = v.drop_many(d_row);
+                    let mut columns = Vec::<Series>::default();
+                    for s in values.get_columns() {
+                        let mut s = s.clone();
+                        if multi {
+                            let name = format!("{}{}{}", ele.label(), PIVOT_SEPARATOR, s.name());
+                            s.rename(&name);
                         }
-                        Err(_) => {}
+                        columns.push(s);
                     }
+                    // 同一数据集按相同行维度透视, 行顺序一致, 可直接横向拼接
+                    pivot_df = Some(match pivot_df {
+                        Some(p) => p.hstack(&columns)?,
+                        None => v.select(d_row)?.hstack(&columns)?,
+                    });
+                }
+                if let Some(v) = pivot_df {
+                    lf = v
+                        .sort(
+                            d_row,
+                            SortMultipleOptions::new().with_order_descending(false),
+                        )?
+                        .lazy();
                 }
             }
         }
         // 聚合表
-        else if query.dimensions.rows.len() > 0 {
+        else if !d_row.is_empty() {
             let mut dims = Vec::<Expr>::default();
             let mut sorts = Vec::<String>::default();
-            for ele in query.dimensions.rows {
+            for ele in d_row {
                 dims.push(col(&ele));
                 sorts.push(ele);
             }
 
             let mut aggs = Vec::<Expr>::default();
+            let mut rates = Vec::<Expr>::default();
             for ele in query.metrics {
                 let epx: Expr;
                 match ele.mode {
@@ -631,40 +1733,367 @@ impl StateStore {
                     MetricMode::Avg => {
                         epx = col(&ele.index).mean();
                     }
-                    MetricMode::Rate => todo!(),
+                    MetricMode::Rate => {
+                        let name = ele.label();
+                        epx = Self::rate_agg(&schema, &ele.index);
+                        rates.push(
+                            binary_expr(col(&name), Operator::TrueDivide, col(&name).sum())
+                                .alias(&name),
+                        );
+                    }
                     _ => {
                         epx = col(&ele.index).count();
                     }
                 }
-                aggs.push(epx);
+                aggs.push(epx.alias(&ele.label()));
             }
-            df = df
-                .lazy()
-                .group_by(dims)
-                .agg(aggs)
-                .sort(
+            // 聚合结果较小, 计算一次后保留在内存中供翻页
+            lf = job
+                .collect(lf.group_by(dims).agg(aggs).with_columns(rates).sort(
                     sorts,
                     SortMultipleOptions::new().with_order_descending(false),
-                )
-                .collect()
-                .unwrap();
+                ))?
+                .lazy();
         }
 
-        log::debug!("{}", &df);
-        self.result = df;
-        self.result.clone().head(Some(30))
+        Ok(lf)
+    }
+
+    // 当前查询结果
+    pub fn result(&self) -> LazyFrame {
+        self.result.plan.clone()
+    }
+
+    // 当前查询结果的分页读取, 重新查询后原有的分页缓存不再保存到数据集
+    pub fn pages(&self) -> Arc<SearchResult> {
+        self.result.clone()
+    }
+
+    pub fn set_result(&mut self, result: SearchResult) {
+        self.result = Arc::new(result);
     }
 
     // 获取结果数据的指定行集
-    pub fn records(&mut self, start: i64, limit: usize) -> DataFrame {
-        self.result.slice(start, limit)
+    pub fn records(
+        result: LazyFrame,
+        start: i64,
+        limit: usize,
+        job: &Job,
+    ) -> Result<DataFrame, DataboardError> {
+        let ret = job.collect(result.slice(start, limit as IdxSize))?;
+        log::debug!("{}", &ret);
+        Ok(ret)
     }
 
     // 数据转化为JSON字符串
-    pub fn to_string(&mut self, df: &mut DataFrame) -> String {
+    pub fn to_string(df: &mut DataFrame) -> Result<String, DataboardError> {
+        let mut df = convert::json_frame(df)?;
         let mut buf: BufWriter<Vec<u8>> = BufWriter::new(Vec::new());
         let mut write = JsonWriter::new(buf.by_ref()).with_json_format(JsonFormat::Json);
-        let _ = write.finish(df);
-        String::from_utf8(buf.into_inner().unwrap()).unwrap()
+        write.finish(&mut df)?;
+        let bytes = buf.into_inner().map_err(|e| e.into_error())?;
+        String::from_utf8(bytes).map_err(|e| DataboardError::Parse(e.to_string()))
+    }
+}
+
+// 展开文件列表: 目录读取其中支持格式的文件, 含通配符时按模式匹配, 同一文件只读一次
+fn expand_paths(paths: &[String]) -> Result<Vec<PathBuf>, DataboardError> {
+    let mut ret: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let mut files: Vec<PathBuf> = if Path::new(path).is_dir() {
+            std::fs::read_dir(path)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|v| v.is_file() && FileFormat::from_extension(v).is_some())
+                .collect()
+        } else if path.contains(['*', '?', '[']) {
+            glob::glob(path)?
+                .collect::<Result<Vec<PathBuf>, _>>()?
+                .into_iter()
+                .filter(|v| v.is_file())
+                .collect()
+        } else {
+            vec![PathBuf::from(path)]
+        };
+        files.sort();
+        for file in files {
+            if !ret.contains(&file) {
+                ret.push(file);
+            }
+        }
+    }
+    if ret.is_empty() {
+        return Err(DataboardError::InvalidQuery(format!(
+            "{} 没有匹配的文件",
+            paths.join("; ")
+        )));
+    }
+    Ok(ret)
+}
+
+// 所有文件的公共目录, 用于生成来源文件的相对路径
+fn common_parent(files: &[PathBuf]) -> PathBuf {
+    let mut base = files
+        .first()
+        .and_then(|v| v.parent())
+        .map(|v| v.to_path_buf())
+        .unwrap_or_default();
+    while !files.iter().all(|v| v.starts_with(&base)) {
+        if !base.pop() {
+            break;
+        }
+    }
+    base
+}
+
+// 合并时同名列的类型: 整数与小数取小数, 日期与日期时间取日期时间, 其余不一致时取字符串
+fn widen_type(left: &DataType, right: &DataType) -> DataType {
+    match (left, right) {
+        (l, r) if l == r => l.clone(),
+        (DataType::Null, v) | (v, DataType::Null) => v.clone(),
+        (l, r) if l.is_integer() && r.is_integer() => DataType::Int64,
+        (l, r) if l.is_numeric() && r.is_numeric() => DataType::Float64,
+        (DataType::Date, DataType::Datetime(unit, tz))
+        | (DataType::Datetime(unit, tz), DataType::Date) => DataType::Datetime(*unit, tz.clone()),
+        _ => DataType::String,
+    }
+}
+
+// 纵向合并多个数据, 按列名对齐, 缺失的列补空值
+fn union_frames(frames: Vec<LazyFrame>) -> Result<LazyFrame, DataboardError> {
+    let schemas = frames
+        .iter()
+        .map(|lf| lf.schema())
+        .collect::<PolarsResult<Vec<SchemaRef>>>()?;
+    let mut schema: Vec<(String, DataType)> = Vec::new();
+    for own in &schemas {
+        for (name, dtype) in own.iter() {
+            match schema.iter_mut().find(|(v, _)| v == name.as_str()) {
+                Some((_, v)) => *v = widen_type(v, dtype),
+                None => schema.push((name.to_string(), dtype.clone())),
+            }
+        }
+    }
+
+    let frames: Vec<LazyFrame> = frames
+        .into_iter()
+        .zip(schemas.iter())
+        .map(|(lf, own)| {
+            let exprs: Vec<Expr> = schema
+                .iter()
+                .map(|(name, dtype)| match own.get(name) {
+                    Some(_) => col(name).cast(dtype.clone()),
+                    None => lit(NULL).cast(dtype.clone()).alias(name),
+                })
+                .collect();
+            lf.select(exprs)
+        })
+        .collect();
+    Ok(concat(frames, UnionArgs::default())?)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DatasetInfo {
+    pub id: String,
+    pub source: String,
+    pub rows: usize,
+    pub columns: usize,
+    // 是否已完成数据配置
+    pub ready: bool,
+}
+
+#[derive(Default)]
+pub struct StateStore {
+    // 已加载的数据集
+    datasets: HashMap<String, Dataset>,
+    // 已加入的数据集数量, 用作数据集的序号
+    inserted: u64,
+}
+impl StateStore {
+    // 添加数据集并返回其名称; replace 时替换同名数据集, 否则重名时依次加后缀, 如 sales-2
+    pub fn insert(&mut self, id: &str, mut dataset: Dataset, replace: bool) -> String {
+        self.inserted += 1;
+        dataset.version = self.inserted;
+        let mut name = id.to_string();
+        let mut n = 1;
+        while !replace && self.datasets.contains_key(&name) {
+            n += 1;
+            name = format!("{}-{}", id, n);
+        }
+        self.datasets.insert(name.clone(), dataset);
+        name
+    }
+
+    // 移除数据集
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.datasets.remove(id).is_some()
+    }
+
+    // 获取数据集
+    pub fn dataset(&mut self, id: &str) -> Result<&mut Dataset, DataboardError> {
+        self.datasets
+            .get_mut(id)
+            .ok_or_else(|| DataboardError::InvalidQuery(format!("数据集 {} 不存在", id)))
+    }
+
+    // 获取锁外计算开始时的数据集, 期间被替换或移除时报错, 避免结果写入其他数据
+    pub fn dataset_at(&mut self, id: &str, version: u64) -> Result<&mut Dataset, DataboardError> {
+        let data = self.dataset(id)?;
+        if data.version != version {
+            return Err(DataboardError::InvalidQuery(format!(
+                "数据集 {} 已重新加载, 请重试",
+                id
+            )));
+        }
+        Ok(data)
+    }
+
+    // 数据集的原始数据计划
+    pub fn records(&mut self, id: &str) -> Result<LazyFrame, DataboardError> {
+        Ok(self.dataset(id)?.records.clone())
+    }
+
+    // 已加载的数据集列表
+    pub fn list(&self) -> Vec<DatasetInfo> {
+        let mut ret: Vec<DatasetInfo> = self.datasets.iter().map(|(id, v)| v.info(id)).collect();
+        ret.sort_by(|a, b| a.id.cmp(&b.id));
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_keeps_datasets_with_same_name() {
+        let mut store = StateStore::default();
+        assert_eq!(store.insert("sales", Dataset::default(), false), "sales");
+        assert_eq!(store.insert("sales", Dataset::default(), false), "sales-2");
+        assert_eq!(store.insert("sales", Dataset::default(), false), "sales-3");
+        assert_eq!(store.insert("sales", Dataset::default(), true), "sales");
+        assert_eq!(store.list().len(), 3);
+    }
+
+    fn ids(df: DataFrame) -> Vec<i32> {
+        df.column("id")
+            .unwrap()
+            .i32()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    #[test]
+    fn pages_read_growing_prefix() {
+        let df = df!("id" => (0..250).collect::<Vec<i32>>()).unwrap();
+        let result = SearchResult::new(df.lazy());
+        let job = Job::default();
+        assert_eq!(
+            ids(result.page(0, 30, &job).unwrap()),
+            (0..30).collect::<Vec<_>>()
+        );
+        assert_eq!(result.cache.lock().unwrap().frame.height(), 30);
+        // 缓存按翻倍的行数增长, 读完全部结果后不再执行计划
+        assert_eq!(
+            ids(result.page(30, 100, &job).unwrap()),
+            (30..130).collect::<Vec<_>>()
+        );
+        assert_eq!(result.cache.lock().unwrap().frame.height(), 130);
+        assert_eq!(
+            ids(result.page(130, 100, &job).unwrap()),
+            (130..230).collect::<Vec<_>>()
+        );
+        assert!(result.cache.lock().unwrap().complete);
+        assert_eq!(
+            ids(result.page(230, 100, &job).unwrap()),
+            (230..250).collect::<Vec<_>>()
+        );
+        assert_eq!(result.page(300, 100, &job).unwrap().height(), 0);
+    }
+
+    #[test]
+    fn page_slice_pushed_down_to_scan() {
+        let path = std::env::temp_dir().join("databoard_page_slice.csv");
+        let text: String = (0..1000).map(|v| format!("{}\n", v)).collect();
+        std::fs::write(&path, format!("id\n{}", text)).unwrap();
+        // 数据配置的类型转换为逐行的 map, 不影响行数限制下推
+        let scan = LazyCsvReader::new(&path).finish().unwrap().with_columns([
+            (col("id") * lit(2)).alias("double"),
+            col("id")
+                .map(|s| Ok(Some(s)), GetOutput::same_type())
+                .alias("mapped"),
+        ]);
+        let plan = SearchResult::new(scan)
+            .prefix(260)
+            .describe_optimized_plan()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(plan.contains("N_ROWS: 260"), "{}", plan);
+    }
+
+    #[test]
+    fn reject_converts_once() {
+        let records = df!(
+            "id" => [Some("1"), Some("x"), Some("3"), None],
+            "name" => ["a", "b", "c", "d"],
+        )
+        .unwrap()
+        .lazy();
+        let setting: Setting =
+            serde_json::from_str(r#"{"columns":{"id":1,"name":0},"errors":{"id":2}}"#).unwrap();
+        let (standard, report) = Dataset::etl(records, None, setting, &Job::default()).unwrap();
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.rejected, 1);
+        assert_eq!(report.columns[0].null, 1);
+        assert_eq!(report.columns[0].failed, 1);
+
+        // 过滤和输出共用临时列, 计划中只有一次转换
+        let plan = standard.clone().describe_optimized_plan().unwrap();
+        assert_eq!(plan.matches(r#"col("id").map()"#).count(), 1, "{}", plan);
+        let df = standard.collect().unwrap();
+        assert_eq!(df.get_column_names(), vec!["id", "name"]);
+        assert_eq!(
+            df.column("id")
+                .unwrap()
+                .i32()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(3), None]
+        );
+    }
+
+    #[test]
+    fn replaced_dataset_rejects_stale_result() {
+        let mut store = StateStore::default();
+        store.insert("sales", Dataset::default(), false);
+        let version = store.dataset("sales").unwrap().version();
+        assert!(store.dataset_at("sales", version).is_ok());
+        store.insert("sales", Dataset::default(), true);
+        assert!(matches!(
+            store.dataset_at("sales", version),
+            Err(DataboardError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn dimension_label_conflicts_with_column() {
+        let schema = Schema::from_iter([
+            Field::new("day", DataType::Date),
+            Field::new("day(月)", DataType::String),
+        ]);
+        let dimension: Dimension =
+            serde_json::from_str(r#"{"rows":["day",{"index":"day","grain":2}],"columns":[]}"#)
+                .unwrap();
+        assert!(matches!(
+            dimension.prepare(&schema),
+            Err(DataboardError::InvalidQuery(v)) if v.contains("day(月)")
+        ));
+
+        let schema = Schema::from_iter([Field::new("day", DataType::Date)]);
+        let plan = dimension.prepare(&schema).unwrap();
+        assert_eq!(plan.rows, vec!["day".to_string(), "day(月)".to_string()]);
+        assert_eq!(plan.buckets.len(), 1);
     }
 }
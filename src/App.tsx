@@ -12,7 +12,7 @@ import { IconClose, IconPlus, IconSettings, IconShareExternal } from '@arco-desi
 
 import * as ReactVTable from '@visactor/react-vtable';
 import { Services } from "./utils";
-import { ISetting, ColumnType, SearchItem, ServiceColumnType, Filter, FilterMode, MetricMode, Metric } from "./types";
+import { ISetting, ColumnType, SearchItem, ServiceColumnType, ServiceError, EtlReport, Filter, FilterMode, MetricMode, Metric } from "./types";
 
 declare interface IDataset {
     /**
@@ -43,6 +43,15 @@ const DatasetDefault: IDataset = {
     records: []
 }
 
+// 显示服务端返回的错误
+function showError(title: string, e: any) {
+    let err = e as ServiceError;
+    Notification.error({
+        title,
+        content: err?.code ? `[${err.code}] ${err.message} ${err.detail ?? ''}` : String(e),
+    })
+}
+
 function App() {
     const [main, _] = useState<Window>(getCurrent());
     const [version, setVersion] = useState("-");
@@ -66,97 +75,107 @@ function App() {
 
     // 打开CSV文本文件并做初始配置
     async function openCsv() {
-        let file = await utils.dialog.open({ multiple: false, defaultPath: await utils.path.appLocalDataDir(), filters: [{ name: "CSV 文本数据", extensions: ["csv"] }] })
+        let file = await utils.dialog.open({ multiple: false, defaultPath: await utils.path.appLocalDataDir(), filters: [{ name: "CSV 文本数据", extensions: ["csv"] }, { name: "Excel 工作簿", extensions: ["xlsx", "xls"] }, { name: "Parquet / JSON Lines / Arrow", extensions: ["parquet", "ndjson", "jsonl", "arrow", "ipc"] }] })
         if (file) {
             setDataset({ ...dataset, loading: true });
-            let ret = await Services.load(file.path);
-            if (ret) {
-                // 源数据列
-                let columns: ServiceColumnType[] = await Services.columns();
-
-                // 预览数据
-                let records: any[] = await Services.preview();
-
-                // 加载数据完成
-                setDataset({ ...dataset, csv: file.path, loading: false, records, columns: columns.map(x => x.name) })
-
-                // 简单数据类型推断
-                let col = columns.map((x: ServiceColumnType) => {
-                    return {
-                        field: x.name,
-                        title: x.name,
-                        width: 'auto'
-                    }
-                })
-                let types = (t: string, x: any) => {
-                    if (t == "Int64" && x > 2147483647) {
-                        x = String(x)
-                    }
-                    if (typeof (x) == 'string') {
-                        x = x.trim()
-                        if (x.length >= 8) {
-                            let d = x.match(/^(\d{2}|\d{4})[-年\.\/]*(\d{2})[-\.\/月]*(\d{2})[日]*$/)
-                            if (d) {
-                                return ColumnType.Date
-                            }
+            try {
+                let ret = await Services.load(file.path);
+                if (ret) {
+                    // 源数据列
+                    let columns: ServiceColumnType[] = await Services.columns();
+
+                    // 预览数据
+                    let records: any[] = await Services.preview();
+
+                    // 加载数据完成
+                    setDataset({ ...dataset, csv: file.path, loading: false, records, columns: columns.map(x => x.name) })
+
+                    // 简单数据类型推断
+                    let col = columns.map((x: ServiceColumnType) => {
+                        return {
+                            field: x.name,
+                            title: x.name,
+                            width: 'auto'
+                        }
+                    })
+                    let types = (t: string, x: any) => {
+                        if (t == "Int64" && x > 2147483647) {
+                            x = String(x)
                         }
-                        if (x.length > 9) {
+                        if (typeof (x) == 'string') {
+                            x = x.trim()
+                            if (x.length >= 8) {
+                                let d = x.match(/^(\d{2}|\d{4})[-年\.\/]*(\d{2})[-\.\/月]*(\d{2})[日]*$/)
+                                if (d) {
+                                    return ColumnType.Date
+                                }
+                            }
+                            if (x.length > 9) {
+                                return ColumnType.String
+                            }
+                            let y = x.match(/^[0-9]+$/);
+                            if (y) {
+                                let v = parseInt(y[0]);
+                                if (!Number.isNaN(v)) {
+                                    return ColumnType.Int
+                                }
+                            }
+                            let f = x.match(/^[\.0-9%]+$/);
+                            if (f) {
+                                let v = parseFloat(f[0]);
+                                if (!Number.isNaN(v)) {
+                                    return ColumnType.Float
+                                }
+                            }
                             return ColumnType.String
                         }
-                        let y = x.match(/^[0-9]+$/);
-                        if (y) {
-                            let v = parseInt(y[0]);
-                            if (!Number.isNaN(v)) {
+
+                        if (typeof (x) == 'number') {
+                            if (Number.isInteger(x)) {
                                 return ColumnType.Int
                             }
-                        }
-                        let f = x.match(/^[\.0-9%]+$/);
-                        if (f) {
-                            let v = parseFloat(f[0]);
-                            if (!Number.isNaN(v)) {
+                            else {
                                 return ColumnType.Float
                             }
                         }
                         return ColumnType.String
                     }
+                    let ret = columns.map((x) => { return { name: x.name, dtype: x.values.length > 0 ? types(x.datatype, x.values[0]) : ColumnType.String } })
 
-                    if (typeof (x) == 'number') {
-                        if (Number.isInteger(x)) {
-                            return ColumnType.Int
-                        }
-                        else {
-                            return ColumnType.Float
-                        }
-                    }
-                    return ColumnType.String
-                }
-                let ret = columns.map((x) => { return { name: x.name, dtype: x.values.length > 0 ? types(x.datatype, x.values[0]) : ColumnType.String } })
-
-                // 初始化数据结构配置
-                setSetting({ dimensions: { rows: [], columns: [] }, metrics: [], filters: [], rules: [], active: false, columns: new Map(ret.map(ret => [ret.name, ret.dtype])) })
+                    // 初始化数据结构配置
+                    setSetting({ dimensions: { rows: [], columns: [] }, metrics: [], filters: [], rules: [], active: false, columns: new Map(ret.map(ret => [ret.name, ret.dtype])) })
 
-                setColCfg(col)
-                await main.setTitle(`数据表查询-${version} ${file.path}`);
+                    setColCfg(col)
+                    await main.setTitle(`数据表查询-${version} ${file.path}`);
 
-            }
-            else {
-                setDataset({ ...dataset, csv: undefined, loading: false })
-                await main.setTitle(`数据表查询-v${version}`);
+                }
+                else {
+                    setDataset({ ...dataset, csv: undefined, loading: false })
+                    await main.setTitle(`数据表查询-v${version}`);
+                }
+            } catch (e) {
+                showError('文件加载失败', e);
+            } finally {
+                setDataset(d => ({ ...d, loading: false }));
             }
         }
     }
 
     // 保存CSV文本文件
     async function saveCsv() {
-        let file = await utils.dialog.save({ title: "保存文件", defaultPath: await utils.path.appLocalDataDir(), filters: [{ name: "CSV 文本数据", extensions: ["csv"] }] })
+        let file = await utils.dialog.save({ title: "保存文件", defaultPath: await utils.path.appLocalDataDir(), filters: [{ name: "CSV 文本数据", extensions: ["csv"] }, { name: "Parquet", extensions: ["parquet"] }, { name: "JSON Lines", extensions: ["ndjson"] }, { name: "Arrow IPC", extensions: ["arrow"] }] })
         if (file) {
-            let ret = await Services.actionSave(file);
-            if (ret) {
-                Notification.info({
-                    closable: false,
-                    title: '文件保存成功',
-                    content: `结果文件已保存到${file}`,
-                })
+            try {
+                let ret = await Services.actionSave(file);
+                if (ret) {
+                    Notification.info({
+                        closable: false,
+                        title: '文件保存成功',
+                        content: `结果文件已保存到${file}`,
+                    })
+                }
+            } catch (e) {
+                showError('文件保存失败', e);
             }
         }
     }
@@ -164,17 +183,27 @@ function App() {
     // 确认保存配置信息
     async function config() {
         console.info(setting);
-        let ret = await Services.actionSetting(setting);
+        let ret: EtlReport;
+        try {
+            ret = await Services.actionSetting(setting);
+        } catch (e) {
+            showError('数据配置失败', e);
+            return;
+        }
         if (ret) {
 
             console.info(ret);
             await onSearch(true);
 
             let filters = new Map<string, string[]>();
-            await Promise.all(setting.filters.map(async x => {
-                let values = await Services.column(x.index);
-                filters.set(x.index, values);
-            }))
+            try {
+                await Promise.all(setting.filters.map(async x => {
+                    let values = await Services.column(x.index);
+                    filters.set(x.index, values);
+                }))
+            } catch (e) {
+                showError('读取过滤值失败', e);
+            }
             setColValues(filters);
             setSetting({ ...setting, active: true });
         }
@@ -185,7 +214,15 @@ function App() {
     async function onSearch(all = false) {
         setDataset({ ...dataset, reading: true });
 
-        let ret = await Services.actionSearch(setting, all ? [] : search);
+        let ret: any;
+        try {
+            ret = await Services.actionSearch(setting, all ? [] : search);
+        } catch (e) {
+            showError('查询失败', e);
+            return;
+        } finally {
+            setDataset(d => ({ ...d, reading: false }));
+        }
 
         setDataset({ ...dataset, reading: false, records: ret.records })
 
@@ -204,7 +241,13 @@ function App() {
     }
     // 分页查询更多数据
     async function onSearchMore(start: number) {
-        let records = await Services.actionSearchMore(start);
+        let records: any[];
+        try {
+            records = await Services.actionSearchMore(start);
+        } catch (e) {
+            showError('查询失败', e);
+            return;
+        }
         let ostart = dataset.records?.length;
         if (ostart == start && records.length > 0) {
             setDataset({ ...dataset, records: dataset.records?.concat(records) })
@@ -522,7 +565,7 @@ function App() {
                         <Select
                             addBefore='行维度'
                             placeholder=''
-                            defaultValue={setting.dimensions.rows}
+                            defaultValue={setting.dimensions.rows.map(x => typeof x == 'string' ? x : x.index)}
                             style={{ width: 600 }}
                             mode="multiple"
                             tokenSeparators={[',', '|', '/']}
@@ -539,7 +582,7 @@ function App() {
                         </Select>
                         <Select addBefore='列维度'
                             style={{ width: 600 }}
-                            defaultValue={setting.dimensions.columns}
+                            defaultValue={setting.dimensions.columns.map(x => typeof x == 'string' ? x : x.index)}
                             mode='multiple'
                             tokenSeparators={[',', '|', '/']}
                             allowClear={true}
@@ -3,7 +3,10 @@
  */
 export declare type ServiceColumnType = {
     name: string,
-    datatype: string,
+    /**
+     * 数据类型, 带参数的类型为对象, 如 { Datetime: ["Milliseconds", null] }
+     */
+    datatype: any,
     values: any[],
 }
 
@@ -27,6 +30,97 @@ export enum ColumnType {
      * 日期类型
      */
     Date = 3,
+    /**
+     * 布尔类型
+     */
+    Boolean = 4,
+    /**
+     * 日期时间类型, 可配置时间单位和时区
+     */
+    Datetime = 5,
+    /**
+     * 时长类型, 可配置时间单位
+     */
+    Duration = 6,
+    /**
+     * 64位整数类型
+     */
+    Int64 = 7,
+    /**
+     * 64位浮点类型
+     */
+    Float64 = 8,
+    /**
+     * 定点小数类型, 可配置精度和小数位数
+     */
+    Decimal = 9,
+    /**
+     * 分类类型
+     */
+    Categorical = 10,
+}
+
+/**
+ * 时间单位
+ */
+export declare type TimeUnit = "ns" | "us" | "ms";
+
+/**
+ * 带参数的列类型配置
+ */
+export declare type ColumnTypeOptions = {
+    type: ColumnType,
+    /**
+     * 日期时间和时长的时间单位, 默认 ms
+     */
+    unit?: TimeUnit,
+    /**
+     * 日期时间的时区, 如 Asia/Shanghai, 为空时不带时区
+     */
+    tz?: string,
+    /**
+     * 定点小数的总位数, 为空时不限制
+     */
+    precision?: number,
+    /**
+     * 定点小数的小数位数, 默认 2
+     */
+    scale?: number,
+}
+
+/**
+ * 列类型配置, 简单类型直接使用编号
+ */
+export declare type ColumnTypeSetting = ColumnType | ColumnTypeOptions;
+
+/**
+ * 日期维度的时间粒度
+ */
+export enum TimeGrain {
+    Day = 0,
+    /**
+     * ISO 周, 周一起
+     */
+    Week = 1,
+    Month = 2,
+    Quarter = 3,
+    Year = 4,
+    /**
+     * 星期几, 1~7, 周一为 1
+     */
+    Weekday = 5,
+    /**
+     * 一天中的小时, 0~23, 仅日期时间列
+     */
+    Hour = 6,
+}
+
+/**
+ * 指定时间粒度的维度, 结果列名为 列名(粒度), 如 day(月)
+ */
+export declare type DimensionItem = {
+    index: string,
+    grain?: TimeGrain,
 }
 
 /**
@@ -36,11 +130,11 @@ export declare type Dimension = {
     /**
      * 行维度名称数组
      */
-    rows: string[],
+    rows: (string | DimensionItem)[],
     /**
      * 列维度名称数组
      */
-    columns: string[],
+    columns: (string | DimensionItem)[],
 }
 
 /**
@@ -73,6 +167,24 @@ export  enum MetricMode {
     'Rate' = 5, 
 }
 
+/**
+ * 占比计算基数
+ */
+export enum RateBase {
+    /**
+     * 占总计
+     */
+    'Total' = 0,
+    /**
+     * 占行合计(仅透视表)
+     */
+    'Row' = 1,
+    /**
+     * 占列合计(仅透视表)
+     */
+    'Column' = 2,
+}
+
 /**
  * 指标
  */
@@ -84,7 +196,11 @@ export declare type Metric = {
     /**
      * 指标计划方法
      */
-    mode: MetricMode
+    mode: MetricMode,
+    /**
+     * 占比计算基数, 默认占总计
+     */
+    rate?: RateBase,
 }
 
 /**
@@ -118,7 +234,55 @@ export enum FilterMode {
     /**
      * 数据范围
      */
-    "DigitalRange" = 4
+    "DigitalRange" = 4,
+    /**
+     * 包含任一值
+     */
+    "Contains" = 5,
+    /**
+     * 不包含任何值
+     */
+    "NotContains" = 6,
+    /**
+     * 正则匹配
+     */
+    "Regex" = 7,
+    /**
+     * 以任一值结尾
+     */
+    "EndsWith" = 8,
+    /**
+     * 不等于
+     */
+    "NotEqual" = 9,
+    /**
+     * 不在所选值中
+     */
+    "NotIn" = 10,
+    /**
+     * 为空
+     */
+    "IsNull" = 11,
+    /**
+     * 不为空
+     */
+    "IsNotNull" = 12,
+    /**
+     * 大于
+     */
+    "GreaterThan" = 13,
+    /**
+     * 大于等于
+     */
+    "GreaterEqual" = 14,
+    /**
+     * 小于
+     */
+    "LessThan" = 15,
+    /**
+     * 小于等于
+     */
+    "LessEqual" = 16,
 };
 
 /**
@@ -148,9 +312,99 @@ export declare type SearchItem = {
      */
     mode: FilterMode,
     /**
-     * 查询值
+     * 查询值, 范围查询时为起止值, 留空的一端不限
+     */
+    value: string[],
+    /**
+     * 文本比较是否忽略大小写
+     */
+    ignore_case?: boolean,
+    /**
+     * 相对日期, 仅用于日期范围, 设置后忽略 value
+     */
+    relative?: RelativeDate,
+}
+
+/**
+ * 相对于基准日期的日期范围, 均包含基准日期当天
+ */
+export enum RelativeRange {
+    Today = 0,
+    Yesterday = 1,
+    /**
+     * 最近 7 天
+     */
+    Last7Days = 2,
+    /**
+     * 最近 30 天
+     */
+    Last30Days = 3,
+    /**
+     * 本周(周一起)至今
+     */
+    WeekToDate = 4,
+    /**
+     * 本月至今
+     */
+    MonthToDate = 5,
+    /**
+     * 本季度至今
+     */
+    QuarterToDate = 6,
+    /**
+     * 本年至今
      */
-    value: string[]
+    YearToDate = 7,
+}
+
+/**
+ * 相对日期范围的对比期
+ */
+export enum PeriodShift {
+    /**
+     * 当期
+     */
+    Current = 0,
+    /**
+     * 上一期
+     */
+    Previous = 1,
+    /**
+     * 去年同期
+     */
+    LastYear = 2,
+}
+
+export declare type RelativeDate = {
+    range: RelativeRange,
+    shift?: PeriodShift,
+}
+
+/**
+ * 条件组内各条件的组合方式
+ */
+export enum FilterLogic {
+    /**
+     * 全部满足
+     */
+    And = 0,
+    /**
+     * 任一满足
+     */
+    Or = 1,
+    /**
+     * 不满足全部条件
+     */
+    Not = 2,
+}
+
+/**
+ * 条件组, 条件和子组按 logic 组合, 可以嵌套
+ */
+export declare type FilterGroup = {
+    logic?: FilterLogic,
+    items?: SearchItem[],
+    groups?: FilterGroup[],
 }
 
 /**
@@ -160,7 +414,23 @@ export declare interface ISetting {
     /**
      * 数据列配置信息
      */
-    columns: Map<string, ColumnType>,
+    columns: Map<string, ColumnTypeSetting>,
+    /**
+     * 各列转换失败时的处理方式, 未配置时置为空值
+     */
+    errors?: Map<string, FailurePolicy>,
+    /**
+     * 各列文本的解析配置, 未配置时使用内置规则
+     */
+    parse?: Map<string, ParseOptions>,
+    /**
+     * 类型转换之后的清洗步骤, 按顺序执行, 使用原列名
+     */
+    cleaning?: CleanStep[],
+    /**
+     * 清洗之后的列操作
+     */
+    operations?: ColumnOperations,
     /**
      * 数据查询维度配置
      */
@@ -177,8 +447,643 @@ export declare interface ISetting {
      * 数据查询附加计算规则配置
      */
     rules: Rule[],
+    /**
+     * 查询条件树, 与查询项之间为且的关系
+     */
+    condition?: FilterGroup,
+    /**
+     * 相对日期的基准日期, yyyy-mm-dd, 默认为当天
+     */
+    as_of?: string,
     /**
      * 配置是否已确认
      */
     active: boolean,
 }
+
+
+/**
+ * 文件格式, 未指定时按扩展名识别
+ */
+export enum FileFormat {
+    /**
+     * CSV文本
+     */
+    Csv = 0,
+    /**
+     * Excel工作簿(仅读取)
+     */
+    Excel = 1,
+    /**
+     * Parquet列存储
+     */
+    Parquet = 2,
+    /**
+     * JSON Lines
+     */
+    Json = 3,
+    /**
+     * Arrow IPC
+     */
+    Ipc = 4,
+}
+
+/**
+ * Excel读取配置
+ */
+export declare type ExcelOptions = {
+    /**
+     * 工作表名称, 为空时读取第一个工作表
+     */
+    sheet?: string,
+    /**
+     * 首行是否为表头, 默认是
+     */
+    has_header?: boolean,
+    /**
+     * 表头之前跳过的行数
+     */
+    skip_rows?: number,
+}
+
+/**
+ * CSV读取配置
+ */
+export declare type CsvOptions = {
+    /**
+     * 分隔符, 为空时自动识别
+     */
+    separator?: string,
+    /**
+     * 文件编码, 默认 auto(非UTF-8按GBK/GB18030处理)
+     */
+    encoding?: string,
+    /**
+     * 引号字符, null时不处理引号
+     */
+    quote_char?: string | null,
+    /**
+     * 首行是否为表头, 默认是
+     */
+    has_header?: boolean,
+    /**
+     * 表头之前跳过的行数
+     */
+    skip_rows?: number,
+    /**
+     * 注释行前缀
+     */
+    comment_prefix?: string,
+    /**
+     * 视为空值的字符串
+     */
+    null_values?: string[],
+    /**
+     * 类型推断读取的行数, null时读取全部
+     */
+    infer_schema_length?: number | null,
+}
+
+/**
+ * 文件读取配置
+ */
+export declare type LoaderOptions = {
+    format?: FileFormat,
+    csv?: CsvOptions,
+    excel?: ExcelOptions,
+}
+
+/**
+ * 多文件合并加载参数
+ */
+export declare type FilesLoaderOptions = LoaderOptions & {
+    /**
+     * 数据集名称, 默认使用首个文件名或目录名
+     */
+    dataset?: string,
+    /**
+     * 是否添加 __source_file 列记录来源文件
+     */
+    sourceColumn?: boolean,
+}
+
+/**
+ * 已加载的数据集
+ */
+export declare type DatasetInfo = {
+    /**
+     * 数据集名称
+     */
+    id: string,
+    /**
+     * 数据来源文件
+     */
+    source: string,
+    /**
+     * 行数
+     */
+    rows: number,
+    /**
+     * 列数
+     */
+    columns: number,
+    /**
+     * 是否已完成数据配置
+     */
+    ready: boolean,
+}
+
+/**
+ * 数据集关联方式
+ */
+export enum JoinMode {
+    /**
+     * 内连接
+     */
+    Inner = 0,
+    /**
+     * 左连接
+     */
+    Left = 1,
+    /**
+     * 全连接
+     */
+    Outer = 2,
+    /**
+     * 左侧存在匹配的行
+     */
+    Semi = 3,
+    /**
+     * 左侧不存在匹配的行
+     */
+    Anti = 4,
+}
+
+/**
+ * 数据集关联配置
+ */
+export declare type JoinSetting = {
+    /**
+     * 左侧数据集
+     */
+    left: string,
+    /**
+     * 右侧数据集
+     */
+    right: string,
+    /**
+     * 左侧关联列
+     */
+    left_on: string[],
+    /**
+     * 右侧关联列, 为空时与左侧相同
+     */
+    right_on?: string[],
+    /**
+     * 关联方式
+     */
+    mode: JoinMode,
+    /**
+     * 右侧重名列后缀, 默认 _right
+     */
+    suffix?: string,
+    /**
+     * 生成的数据集名称
+     */
+    target: string,
+}
+
+/**
+ * 服务端错误
+ */
+export declare type ServiceError = {
+    /**
+     * 错误编码: io / parse / unknown_column / type_mismatch / invalid_query / cancelled
+     */
+    code: string,
+    /**
+     * 错误提示
+     */
+    message: string,
+    /**
+     * 错误详情
+     */
+    detail: string,
+}
+
+/**
+ * 后台任务阶段
+ */
+export enum JobStage {
+    /**
+     * 读取文件
+     */
+    Read = 0,
+    /**
+     * 统计行数
+     */
+    Count = 1,
+    /**
+     * 类型转换
+     */
+    Transform = 2,
+    /**
+     * 查询计算
+     */
+    Search = 3,
+    /**
+     * 保存结果
+     */
+    Save = 4,
+    /**
+     * 已完成
+     */
+    Done = 5,
+}
+
+/**
+ * 后台任务进度
+ */
+export declare type JobProgress = {
+    /**
+     * 任务编号
+     */
+    id: string,
+    stage: JobStage,
+    /**
+     * 当前阶段已处理的行数
+     */
+    rows: number,
+}
+
+/**
+ * 查询配置
+ */
+export declare type Query = {
+    dimensions: Dimension,
+    metrics: Metric[],
+    filters: Filter[],
+    rules: Rule[],
+    /**
+     * 查询项, 各项之间为且的关系, 已配置为过滤项的列按配置的方式过滤
+     */
+    search: SearchItem[],
+    /**
+     * 条件树, 与查询项之间为且的关系
+     */
+    condition?: FilterGroup,
+    /**
+     * 相对日期的基准日期, yyyy-mm-dd, 默认为当天
+     */
+    as_of?: string,
+}
+
+/**
+ * 项目中的数据来源
+ */
+export declare type BoardSource = LoaderOptions & {
+    /**
+     * 数据集名称
+     */
+    dataset: string,
+    /**
+     * 数据文件, 相对路径按项目文件所在目录解析
+     */
+    paths: string[],
+    /**
+     * 多文件合并时是否添加来源文件列
+     */
+    source_column?: boolean,
+    /**
+     * 列类型配置
+     */
+    columns?: { [name: string]: ColumnTypeSetting },
+    /**
+     * 转换失败的处理方式
+     */
+    errors?: { [name: string]: FailurePolicy },
+    /**
+     * 文本解析配置
+     */
+    parse?: { [name: string]: ParseOptions },
+    /**
+     * 清洗步骤
+     */
+    cleaning?: CleanStep[],
+    /**
+     * 列操作
+     */
+    operations?: ColumnOperations,
+}
+
+/**
+ * 保存的查询
+ */
+export declare type BoardQuery = {
+    name: string,
+    /**
+     * 查询的数据集
+     */
+    dataset: string,
+    query: Query,
+}
+
+/**
+ * 项目文件
+ */
+export declare type Board = {
+    /**
+     * 文件格式版本, 保存时由服务端填写
+     */
+    version?: number,
+    /**
+     * 项目名称, 为空时使用文件名
+     */
+    name?: string,
+    sources: BoardSource[],
+    queries: BoardQuery[],
+    /**
+     * 前端布局, 服务端原样保存
+     */
+    layout?: any,
+}
+
+/**
+ * 最近打开的项目
+ */
+export declare type RecentBoard = {
+    path: string,
+    name: string,
+    /**
+     * 最近打开时间, unix 秒
+     */
+    opened: number,
+}
+
+/**
+ * 列类型推荐
+ */
+export declare type TypeSuggestion = {
+    name: string,
+    column_type: ColumnTypeSetting,
+    /**
+     * 完整符合该类型格式的值占比
+     */
+    confidence: number,
+    /**
+     * 按该类型转换时失败的值占比
+     */
+    failed: number,
+    /**
+     * 参与推断的非空值数量
+     */
+    samples: number,
+}
+
+/**
+ * 值无法转换为配置类型时的处理方式
+ */
+export enum FailurePolicy {
+    /**
+     * 置为空值
+     */
+    Null = 0,
+    /**
+     * 使用类型默认值
+     */
+    Default = 1,
+    /**
+     * 排除所在行
+     */
+    Reject = 2,
+    /**
+     * 终止配置
+     */
+    Abort = 3,
+}
+
+/**
+ * 单列文本的解析配置
+ */
+export declare type ParseOptions = {
+    /**
+     * 日期格式(strftime), 按顺序尝试, 如 %d/%m/%Y, 为空时使用内置格式
+     */
+    formats?: string[],
+    /**
+     * 两位年份小于该值时为20xx, 否则为19xx, 未配置时均为20xx
+     */
+    century_pivot?: number,
+    /**
+     * 小数点, 默认 .
+     */
+    decimal?: string,
+    /**
+     * 千分位分隔符, 默认 , null表示没有千分位
+     */
+    thousands?: string | null,
+    /**
+     * 解析前去掉的货币符号, 如 €/$/USD
+     */
+    currency?: string[],
+    /**
+     * 带 % 的值按百分数处理, 除以100
+     */
+    percent?: boolean,
+}
+
+/**
+ * 单列的转换统计
+ */
+export declare type ColumnReport = {
+    name: string,
+    /**
+     * 转换成功的值
+     */
+    converted: number,
+    /**
+     * 原始数据为空的值
+     */
+    null: number,
+    /**
+     * 转换失败后使用默认值的值
+     */
+    defaulted: number,
+    /**
+     * 转换失败的值, 按配置置空或排除所在行
+     */
+    failed: number,
+    /**
+     * 转换失败的原始值示例
+     */
+    samples: string[],
+}
+
+/**
+ * 数据配置的转换报告
+ */
+export declare type EtlReport = {
+    /**
+     * 原始数据行数
+     */
+    rows: number,
+    /**
+     * 因转换失败排除的行数
+     */
+    rejected: number,
+    columns: ColumnReport[],
+    /**
+     * 各清洗步骤影响的行数
+     */
+    cleaning: CleanReport[],
+    /**
+     * 配置后数据的列名, 按列顺序
+     */
+    output: string[],
+}
+
+/**
+ * 拆分一列为多列, 分隔符与正则二选一
+ */
+export declare type SplitColumn = {
+    column: string,
+    /**
+     * 生成的列名, 按分隔符拆分时最后一列保留剩余部分
+     */
+    into: string[],
+    /**
+     * 分隔符
+     */
+    separator?: string,
+    /**
+     * 正则, 捕获分组依次对应生成的列
+     */
+    pattern?: string,
+}
+
+/**
+ * 合并多列为一列
+ */
+export declare type ConcatColumn = {
+    columns: string[],
+    target: string,
+    /**
+     * 连接符, 空值跳过
+     */
+    separator?: string,
+}
+
+/**
+ * 类型转换之后的列操作, 依次为拆分/合并/删除/重命名/排序
+ */
+export declare type ColumnOperations = {
+    split?: SplitColumn[],
+    concat?: ConcatColumn[],
+    /**
+     * 删除的列, 可以是拆分/合并生成的列
+     */
+    drop?: string[],
+    /**
+     * 原列名 -> 新列名
+     */
+    rename?: { [name: string]: string },
+    /**
+     * 列顺序, 使用重命名后的列名, 未列出的列按原顺序排在后面
+     */
+    order?: string[],
+}
+
+/**
+ * 清洗步骤类型
+ */
+export enum CleanMode {
+    /**
+     * 删除重复行
+     */
+    Dedupe = 0,
+    /**
+     * 填充空值
+     */
+    FillNull = 1,
+    /**
+     * 全角字符转为半角
+     */
+    HalfWidth = 2,
+    /**
+     * 转为小写
+     */
+    Lowercase = 3,
+    /**
+     * 转为大写
+     */
+    Uppercase = 4,
+    /**
+     * 连续空白合并为一个空格, 并去掉首尾空白
+     */
+    Whitespace = 5,
+    /**
+     * 按分位数截断异常值
+     */
+    Clip = 6,
+}
+
+/**
+ * 空值填充方式
+ */
+export enum FillMode {
+    /**
+     * 使用填充值
+     */
+    Constant = 0,
+    /**
+     * 使用上一个非空值
+     */
+    Forward = 1,
+    /**
+     * 使用下一个非空值
+     */
+    Backward = 2,
+    /**
+     * 使用平均值
+     */
+    Mean = 3,
+    /**
+     * 使用中位数
+     */
+    Median = 4,
+}
+
+/**
+ * 清洗步骤
+ */
+export declare type CleanStep = {
+    mode: CleanMode,
+    /**
+     * 处理的列, 为空时: 去重比较整行, 文本步骤处理全部文本列, 截断处理全部数值列, 填充处理全部列
+     */
+    columns?: string[],
+    /**
+     * 空值填充方式, 默认使用填充值
+     */
+    fill?: FillMode,
+    /**
+     * 填充值, 按列类型转换
+     */
+    value?: string,
+    /**
+     * 截断的下分位数, 0~1
+     */
+    lower?: number,
+    /**
+     * 截断的上分位数, 0~1
+     */
+    upper?: number,
+}
+
+/**
+ * 清洗步骤影响的行数
+ */
+export declare type CleanReport = {
+    mode: CleanMode,
+    affected: number,
+}
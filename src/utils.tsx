@@ -1,56 +1,116 @@
 import { getVersion } from '@tauri-apps/api/app';
 
 import * as core from "@tauri-apps/api/core";
+import * as event from "@tauri-apps/api/event";
 import * as path from "@tauri-apps/api/path";
 import * as dialog from "@tauri-apps/plugin-dialog";
 import * as clipboard from '@tauri-apps/plugin-clipboard-manager';
 
-import { ISetting, SearchItem, ServiceColumnType } from './types';
+import { Board, DatasetInfo, EtlReport, FileFormat, FilesLoaderOptions, ISetting, JobProgress, JoinSetting, LoaderOptions, RecentBoard, SearchItem, ServiceColumnType, TypeSuggestion } from './types';
+
+// 当前数据集
+let current = "";
+
+// 耗时命令可指定任务编号, 用于接收进度和取消
+const JOB_EVENT = "databoard://job";
 
 const Services = {
-    load: async (file: string) => {
-        let ret = await core.invoke("databoard_loader", { path: file });
-        return ret;
+    load: async (file: string, options?: LoaderOptions, job?: string) => {
+        current = await core.invoke("databoard_loader", { path: file, ...options, job });
+        return current;
+    },
+    // 合并加载多个文件, 支持目录和通配符
+    loadFiles: async (files: string[], options?: FilesLoaderOptions, job?: string) => {
+        current = await core.invoke("databoard_loader_files", { paths: files, ...options, job });
+        return current;
+    },
+    datasets: async (): Promise<DatasetInfo[]> => {
+        return await core.invoke("databoard_datasets", {});
+    },
+    use: (dataset: string) => {
+        current = dataset;
+    },
+    remove: async (dataset: string): Promise<boolean> => {
+        return await core.invoke("databoard_remove", { dataset });
+    },
+    join: async (setting: JoinSetting, job?: string): Promise<DatasetInfo> => {
+        return await core.invoke("databoard_join", { setting, job });
     },
-    count: async () => {
-        return await core.invoke("databoard_count", {});
+    // 取消任务, 任务不存在或已结束时返回 false
+    cancel: async (job: string): Promise<boolean> => {
+        return await core.invoke("databoard_cancel", { job });
+    },
+    // 监听任务进度, 返回取消监听的函数
+    onProgress: async (handler: (progress: JobProgress) => void) => {
+        return await event.listen<JobProgress>(JOB_EVENT, (e) => handler(e.payload));
+    },
+    // 保存项目文件
+    saveBoard: async (file: string, board: Board): Promise<boolean> => {
+        return await core.invoke("databoard_board_save", { path: file, board });
+    },
+    openBoard: async (file: string): Promise<Board> => {
+        return await core.invoke("databoard_board_open", { path: file });
+    },
+    // 最近打开的项目
+    recentBoards: async (): Promise<RecentBoard[]> => {
+        return await core.invoke("databoard_board_recent", {});
+    },
+    sheets: async (file: string): Promise<string[]> => {
+        return await core.invoke("databoard_sheets", { path: file });
+    },
+    count: async (job?: string) => {
+        return await core.invoke("databoard_count", { dataset: current, job });
     },
     columns: async (): Promise<ServiceColumnType[]> => {
-        let ret: any = await core.invoke("databoard_columns", {})
+        let ret: any = await core.invoke("databoard_columns", { dataset: current })
         return ret.columns;
     },
+    // 推荐各列类型
+    suggestTypes: async (job?: string): Promise<TypeSuggestion[]> => {
+        return await core.invoke("databoard_suggest_types", { dataset: current, job });
+    },
     preview: async (): Promise<any[]> => {
-        return await core.invoke("databoard_preview", { count: 100 });
+        return await core.invoke("databoard_preview", { dataset: current, count: 100 });
     },
     column: async (name: string): Promise<string[]> => {
-        let ret: any = await core.invoke("databoard_unique", { name })
+        let ret: any = await core.invoke("databoard_unique", { dataset: current, name })
         let values: string[] = [];
+        let pad = (x: number) => x < 10 ? `0${x}` : `${x}`;
+        // 日期时间按所在时区的本地时间返回, 单位见类型参数
+        let scale: { [unit: string]: number } = { Milliseconds: 1, Microseconds: 0.001, Nanoseconds: 0.000001 };
         switch (ret.datatype) {
             case "Date":
-                let pad = (x: number) => x < 10 ? `0${x}` : `${x}`;
                 values = ret.values.map((x: number) => x * 86400000).map((x: number) => {
                     let date = new Date(x);
                     return `${date.getFullYear()}-${pad(date.getMonth() + 1)}-${pad(date.getDate())}`
                 })
                 break;
             default:
+                if (ret.datatype?.Datetime) {
+                    let unit = scale[ret.datatype.Datetime[0]] ?? 1;
+                    values = ret.values.map((x: number) => {
+                        let date = new Date(Math.floor(x * unit));
+                        return `${date.getUTCFullYear()}-${pad(date.getUTCMonth() + 1)}-${pad(date.getUTCDate())} ${pad(date.getUTCHours())}:${pad(date.getUTCMinutes())}:${pad(date.getUTCSeconds())}`
+                    })
+                    break;
+                }
                 values = ret.values.map((x: any) => String(x))
                 break;
         }
         return values;
     },
 
-    actionSetting: async (setting: ISetting): Promise<boolean> => {
-        return await core.invoke("databoard_setting", { setting: { columns: setting.columns } });
+    actionSetting: async (setting: ISetting, job?: string): Promise<EtlReport> => {
+        return await core.invoke("databoard_setting", { dataset: current, setting: { columns: setting.columns, errors: setting.errors, parse: setting.parse, cleaning: setting.cleaning, operations: setting.operations }, job });
     },
-    actionSearch: async (setting: ISetting, search: SearchItem[]): Promise<any> => {
-        return await core.invoke("databoard_search", { playload: { ...setting, search } });
+    actionSearch: async (setting: ISetting, search: SearchItem[], job?: string): Promise<any> => {
+        return await core.invoke("databoard_search", { dataset: current, playload: { ...setting, search }, job });
     },
     actionSearchMore: async (start: number): Promise<any[]> => {
-        return await core.invoke("databoard_search_more", { start });
+        return await core.invoke("databoard_search_more", { dataset: current, start });
     },
-    actionSave: async (file: string) => {
-        return await core.invoke("databoard_search_save", { path: file });
+    actionSave: async (file: string, format?: FileFormat, job?: string) => {
+        return await core.invoke("databoard_search_save", { dataset: current, path: file, format, job });
     }
 }
 